@@ -0,0 +1,186 @@
+//! Benchmarks of `sort_with_options()`.
+//!
+//! Throughput is reported in pixels, so `Melem/s` reads as megapixels per second.
+
+use asdf_pixel_sort::{sort_with_options, Direction, Key, Mode, Options};
+use criterion::measurement::WallTime;
+use criterion::{
+    criterion_group, criterion_main, BatchSize, BenchmarkGroup, BenchmarkId, Criterion, Throughput,
+};
+use image::{Rgb, RgbImage};
+
+/// Generates a reproducible noise image.
+fn noise(width: u32, height: u32) -> RgbImage {
+    RgbImage::from_fn(width, height, |x, y| {
+        let mut z = (y as u64) << 32 | x as u64;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        let [r, g, b, ..] = (z ^ (z >> 31)).to_le_bytes();
+        Rgb([r, g, b])
+    })
+}
+
+/// Generates an image of bright pixels only, so each line is one long interval.
+fn all_bright(width: u32, height: u32) -> RgbImage {
+    let image = noise(width, height);
+    RgbImage::from_fn(width, height, |x, y| {
+        let [r, g, b] = image.get_pixel(x, y).0;
+        Rgb([r | 0x80, g | 0x80, b | 0x80])
+    })
+}
+
+/// Generates an image of alternating bright and dark pixels, so intervals are shortest.
+fn alternating(width: u32, height: u32) -> RgbImage {
+    RgbImage::from_fn(width, height, |x, y| {
+        if (x + y) % 2 == 0 {
+            Rgb([255, 255, 255])
+        } else {
+            Rgb([0, 0, 0])
+        }
+    })
+}
+
+fn bench(
+    group: &mut BenchmarkGroup<WallTime>,
+    id: BenchmarkId,
+    image: &RgbImage,
+    options: &Options,
+) {
+    group.throughput(Throughput::Elements(
+        image.width() as u64 * image.height() as u64,
+    ));
+    group.bench_with_input(id, options, |b, options| {
+        b.iter_batched_ref(
+            || image.clone(),
+            |buf| sort_with_options(buf, options),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn bench_mode(c: &mut Criterion) {
+    let image = noise(1024, 1024);
+
+    let mut group = c.benchmark_group("mode");
+    group.sample_size(20);
+
+    let modes = [
+        ("black", Mode::black()),
+        ("brightness", Mode::brightness()),
+        ("white", Mode::white()),
+        ("edge", Mode::edge()),
+    ];
+    for (name, mode) in modes {
+        let options = Options {
+            mode,
+            ..Default::default()
+        };
+        bench(
+            &mut group,
+            BenchmarkId::from_parameter(name),
+            &image,
+            &options,
+        );
+    }
+
+    group.finish();
+}
+
+fn bench_direction(c: &mut Criterion) {
+    let image = noise(1024, 1024);
+
+    let mut group = c.benchmark_group("direction");
+    group.sample_size(20);
+
+    let directions = [
+        ("both", Direction::Both),
+        ("column", Direction::Column),
+        ("row", Direction::Row),
+    ];
+    for (name, direction) in directions {
+        let options = Options {
+            direction,
+            ..Default::default()
+        };
+        bench(
+            &mut group,
+            BenchmarkId::from_parameter(name),
+            &image,
+            &options,
+        );
+    }
+
+    group.finish();
+}
+
+fn bench_key(c: &mut Criterion) {
+    let image = noise(1024, 1024);
+
+    let mut group = c.benchmark_group("key");
+    group.sample_size(20);
+
+    let keys = [
+        ("color", Key::Color),
+        ("brightness", Key::Brightness),
+        ("lightness", Key::Lightness),
+        ("hue", Key::Hue),
+    ];
+    for (name, key) in keys {
+        let options = Options {
+            key,
+            ..Default::default()
+        };
+        bench(
+            &mut group,
+            BenchmarkId::from_parameter(name),
+            &image,
+            &options,
+        );
+    }
+
+    group.finish();
+}
+
+fn bench_size(c: &mut Criterion) {
+    let mut group = c.benchmark_group("size");
+    group.sample_size(10);
+
+    for size in [256, 1024, 2048, 4096] {
+        let image = noise(size, size);
+        let id = BenchmarkId::from_parameter(format!("{}x{}", size, size));
+        bench(&mut group, id, &image, &Options::default());
+    }
+
+    group.finish();
+}
+
+fn bench_pattern(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pattern");
+    group.sample_size(20);
+
+    let patterns = [
+        ("noise", noise(1024, 1024)),
+        ("all_bright", all_bright(1024, 1024)),
+        ("alternating", alternating(1024, 1024)),
+    ];
+    for (name, image) in patterns.iter() {
+        bench(
+            &mut group,
+            BenchmarkId::from_parameter(name),
+            image,
+            &Options::default(),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_mode,
+    bench_direction,
+    bench_key,
+    bench_size,
+    bench_pattern
+);
+criterion_main!(benches);
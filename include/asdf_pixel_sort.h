@@ -0,0 +1,110 @@
+#ifndef ASDF_PIXEL_SORT_H
+#define ASDF_PIXEL_SORT_H
+
+/* Generated with cbindgen. Run `cbindgen --output include/asdf_pixel_sort.h` to update. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Result of C API functions.
+typedef enum AsdfStatus {
+  // Succeeded.
+  ASDF_STATUS_OK = 0,
+  // A pointer is null.
+  ASDF_STATUS_NULL_POINTER,
+  // Width, height or stride doesn't fit the buffer.
+  ASDF_STATUS_INVALID_DIMENSIONS,
+  // Sorting failed unexpectedly.
+  ASDF_STATUS_PANIC,
+  // A format, an option or the size of options is unknown.
+  ASDF_STATUS_INVALID_ARGUMENT,
+} AsdfStatus;
+
+// Layout of pixels in buffers, passed as `u32`.
+typedef enum AsdfFormat {
+  // Red, green and blue of 8 bits.
+  ASDF_FORMAT_RGB = 0,
+  // Red, green, blue and alpha of 8 bits. Alpha values move along with pixels.
+  ASDF_FORMAT_RGBA = 1,
+} AsdfFormat;
+
+// Kinds of [`AsdfMode`], mirroring [`Mode`] and stored as `u32`.
+typedef enum AsdfModeKind {
+  ASDF_MODE_KIND_BLACK = 0,
+  ASDF_MODE_KIND_BRIGHTNESS = 1,
+  ASDF_MODE_KIND_WHITE = 2,
+  ASDF_MODE_KIND_EDGE = 3,
+} AsdfModeKind;
+
+// Directions to sort, mirroring [`Direction`] and stored as `u32`.
+typedef enum AsdfDirection {
+  ASDF_DIRECTION_BOTH = 0,
+  ASDF_DIRECTION_COLUMN = 1,
+  ASDF_DIRECTION_ROW = 2,
+} AsdfDirection;
+
+// Treatments of transparent pixels, mirroring [`Alpha`] and stored as `u32`.
+typedef enum AsdfAlpha {
+  ASDF_ALPHA_IGNORE = 0,
+  ASDF_ALPHA_BREAK = 1,
+  ASDF_ALPHA_EXCLUDE = 2,
+  ASDF_ALPHA_PREMULTIPLY = 3,
+} AsdfAlpha;
+
+// Color, mirroring [`PColor`].
+typedef struct AsdfColor {
+  uint8_t red;
+  uint8_t green;
+  uint8_t blue;
+  uint8_t alpha;
+} AsdfColor;
+
+// Mode to detect intervals, mirroring [`Mode`].
+//
+// `kind` is one of [`AsdfModeKind`]. `color` is used by black and white modes, and `threshold` is
+// used by brightness and edge modes.
+typedef struct AsdfMode {
+  uint32_t kind;
+  struct AsdfColor color;
+  uint8_t threshold;
+} AsdfMode;
+
+// Options of sorting, mirroring [`Options`].
+//
+// `size` is the size of the struct in bytes, set by [`asdf_options_default()`]. Later versions
+// append fields and keep accepting options of earlier sizes, leaving appended fields as defaults.
+// The earliest size ends at `direction`. `direction` is one of [`AsdfDirection`], and `alpha` is
+// one of [`AsdfAlpha`] used for RGBA pixels.
+typedef struct AsdfOptions {
+  size_t size;
+  struct AsdfMode mode;
+  uint32_t direction;
+  uint32_t alpha;
+} AsdfOptions;
+
+// Returns default options, same as [`Options::default()`].
+struct AsdfOptions asdf_options_default(void);
+
+// Sorts pixels of a buffer in place with options.
+//
+// Rows are `stride` bytes apart, and `stride` must be at least `width` times bytes of a pixel.
+// `format` is one of [`AsdfFormat`]. `options` may be null to sort with default options.
+//
+// # Safety
+//
+// `pixels` must point to `stride * height` bytes writable, and `options` must be null or point
+// to options of `size` bytes readable.
+enum AsdfStatus asdf_sort(uint8_t *pixels,
+                          uint32_t width,
+                          uint32_t height,
+                          size_t stride,
+                          uint32_t format,
+                          const struct AsdfOptions *options);
+
+// Returns a static message of the status, one of [`AsdfStatus`], as a null-terminated string.
+const char *asdf_status_message(uint32_t status);
+
+#endif  /* ASDF_PIXEL_SORT_H */
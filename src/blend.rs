@@ -0,0 +1,164 @@
+use crate::{transfer::Decoder, Blend, BlendMode};
+
+/// Maximum weight of a pixel, the product of a full opacity and a full mask.
+const OPAQUE: u32 = 255 * 255;
+
+/// Blends `sorted` RGB subpixels over `original` ones in place, weighting the opacity by `mask`
+/// of the pixels if given. The mask of `blend` is ignored in favor of `mask`.
+///
+/// Pixels are mixed in linear light and re-encoded if `decoder` is given.
+pub(crate) fn blend_pixels(
+    sorted: &mut [u8],
+    original: &[u8],
+    mask: Option<&[u8]>,
+    blend: &Blend,
+    decoder: Option<&Decoder>,
+) {
+    let pixels = sorted.chunks_exact_mut(3).zip(original.chunks_exact(3));
+    for (i, (pixel, original)) in pixels.enumerate() {
+        let weight = blend.opacity as u32 * mask.map_or(255, |mask| mask[i] as u32);
+        for (channel, (value, &base)) in pixel.iter_mut().zip(original.iter()).enumerate() {
+            *value = mix_weighted(decoder, blend.mode, channel, base, *value, (weight, OPAQUE));
+        }
+    }
+}
+
+/// Mixes a subpixel `top` of the channel over `base` with the blend mode, weighting the mixed
+/// value by `weight` out of `total` against `base`.
+///
+/// Subpixels are mixed in linear light and re-encoded if `decoder` is given.
+pub(crate) fn mix_weighted(
+    decoder: Option<&Decoder>,
+    mode: BlendMode,
+    channel: usize,
+    base: u8,
+    top: u8,
+    (weight, total): (u32, u32),
+) -> u8 {
+    match decoder {
+        None => {
+            let mixed = mix(mode, base, top) as u32;
+            ((base as u32 * (total - weight) + mixed * weight + total / 2) / total) as u8
+        }
+        Some(decoder) => {
+            let base = decoder.decode_value(channel, base);
+            let mixed = mix_linear(mode, base, decoder.decode_value(channel, top));
+            let t = weight as f32 / total as f32;
+            decoder.encode_value(channel, base + (mixed - base) * t)
+        }
+    }
+}
+
+/// Mixes a channel of the sorted pixel `top` over the original pixel `base` with the blend mode.
+fn mix(mode: BlendMode, base: u8, top: u8) -> u8 {
+    let (a, b) = (base as u32, top as u32);
+    let value = match mode {
+        BlendMode::Normal => b,
+        BlendMode::Screen => 255 - div255((255 - a) * (255 - b)),
+        BlendMode::Multiply => div255(a * b),
+        BlendMode::Lighten => a.max(b),
+        BlendMode::Darken => a.min(b),
+        BlendMode::Difference => a.max(b) - a.min(b),
+    };
+    value as u8
+}
+
+/// Mixes linear light of the sorted pixel `top` over the original pixel `base` with the blend
+/// mode, as [`mix()`] does for encoded values.
+fn mix_linear(mode: BlendMode, base: f32, top: f32) -> f32 {
+    let (a, b) = (base, top);
+    match mode {
+        BlendMode::Normal => b,
+        BlendMode::Screen => 1.0 - (1.0 - a) * (1.0 - b),
+        BlendMode::Multiply => a * b,
+        BlendMode::Lighten => a.max(b),
+        BlendMode::Darken => a.min(b),
+        BlendMode::Difference => (a - b).abs(),
+    }
+}
+
+/// Divides by `255` with rounding.
+fn div255(value: u32) -> u32 {
+    (value + 127) / 255
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Transfer;
+
+    fn blended(mode: BlendMode, opacity: u8, mask: Option<&[u8]>) -> Vec<u8> {
+        let mut sorted = vec![200, 100, 0, 64, 128, 255];
+        let original = [100, 200, 255, 64, 0, 128];
+        let blend = Blend {
+            mode,
+            opacity,
+            mask: None,
+        };
+        blend_pixels(&mut sorted, &original, mask, &blend, None);
+        sorted
+    }
+
+    #[test]
+    fn test_mix() {
+        assert_eq!(200, mix(BlendMode::Normal, 100, 200));
+        assert_eq!(222, mix(BlendMode::Screen, 100, 200));
+        assert_eq!(78, mix(BlendMode::Multiply, 100, 200));
+        assert_eq!(200, mix(BlendMode::Lighten, 100, 200));
+        assert_eq!(100, mix(BlendMode::Darken, 100, 200));
+        assert_eq!(100, mix(BlendMode::Difference, 200, 100));
+        assert_eq!(255, mix(BlendMode::Screen, 0, 255));
+        assert_eq!(0, mix(BlendMode::Multiply, 0, 255));
+    }
+
+    #[test]
+    fn test_blend_pixels_opacity() {
+        let expected = [200, 100, 0, 64, 128, 255];
+        assert_eq!(expected, blended(BlendMode::Normal, 255, None)[..]);
+
+        let expected = [100, 200, 255, 64, 0, 128];
+        assert_eq!(expected, blended(BlendMode::Normal, 0, None)[..]);
+
+        let expected = [150, 150, 127, 64, 64, 192];
+        assert_eq!(expected, blended(BlendMode::Normal, 128, None)[..]);
+
+        let expected = [100, 100, 255, 0, 128, 127];
+        assert_eq!(expected, blended(BlendMode::Difference, 255, None)[..]);
+    }
+
+    #[test]
+    fn test_blend_pixels_linear() {
+        let blend = Blend {
+            opacity: 128,
+            ..Default::default()
+        };
+        let blend_with = |transfer: &Transfer| {
+            let mut sorted = vec![255, 0, 128];
+            let decoder = Decoder::new(transfer);
+            blend_pixels(&mut sorted, &[0, 255, 128], None, &blend, decoder.as_ref());
+            sorted
+        };
+        assert_eq!(vec![128, 127, 128], blend_with(&Transfer::Encoded));
+        assert_eq!(vec![188, 187, 128], blend_with(&Transfer::Srgb));
+
+        let decoder = Decoder::new(&Transfer::Srgb).unwrap();
+        let mixed = mix_weighted(Some(&decoder), BlendMode::Multiply, 0, 255, 128, (1, 1));
+        assert_eq!(128, mixed, "multiplied by white");
+    }
+
+    #[test]
+    fn test_blend_pixels_mask() {
+        let expected = [100, 200, 255, 64, 128, 255];
+        assert_eq!(
+            expected,
+            blended(BlendMode::Normal, 255, Some(&[0, 255]))[..]
+        );
+
+        let expected = [100, 200, 255, 64, 64, 192];
+        assert_eq!(
+            expected,
+            blended(BlendMode::Normal, 255, Some(&[0, 128]))[..]
+        );
+    }
+}
@@ -0,0 +1,592 @@
+//! C API to sort raw pixel buffers, with a header generated by cbindgen.
+
+use std::convert::TryFrom;
+use std::mem::size_of;
+use std::os::raw::c_char;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use image::{RgbImage, RgbaImage};
+
+use crate::{
+    sort_rgba, sort_with_options, Alpha, Direction, Mode, Options, PColor, DEFAULT_BRIGHTNESS,
+};
+
+/// Result of C API functions.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AsdfStatus {
+    /// Succeeded.
+    Ok = 0,
+    /// A pointer is null.
+    NullPointer,
+    /// Width, height or stride doesn't fit the buffer.
+    InvalidDimensions,
+    /// Sorting failed unexpectedly.
+    Panic,
+    /// A format, an option or the size of options is unknown.
+    InvalidArgument,
+}
+
+/// Layout of pixels in buffers, passed as `u32`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AsdfFormat {
+    /// Red, green and blue of 8 bits.
+    Rgb = 0,
+    /// Red, green, blue and alpha of 8 bits. Alpha values move along with pixels.
+    Rgba = 1,
+}
+
+/// Kinds of [`AsdfMode`], mirroring [`Mode`] and stored as `u32`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AsdfModeKind {
+    Black = 0,
+    Brightness = 1,
+    White = 2,
+    Edge = 3,
+}
+
+/// Directions to sort, mirroring [`Direction`] and stored as `u32`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AsdfDirection {
+    Both = 0,
+    Column = 1,
+    Row = 2,
+}
+
+/// Treatments of transparent pixels, mirroring [`Alpha`] and stored as `u32`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AsdfAlpha {
+    Ignore = 0,
+    Break = 1,
+    Exclude = 2,
+    Premultiply = 3,
+}
+
+/// Color, mirroring [`PColor`].
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AsdfColor {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alpha: u8,
+}
+
+/// Mode to detect intervals, mirroring [`Mode`].
+///
+/// `kind` is one of [`AsdfModeKind`]. `color` is used by black and white modes, and `threshold` is
+/// used by brightness and edge modes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AsdfMode {
+    pub kind: u32,
+    pub color: AsdfColor,
+    pub threshold: u8,
+}
+
+/// Options of sorting, mirroring [`Options`].
+///
+/// `size` is the size of the struct in bytes, set by [`asdf_options_default()`]. Later versions
+/// append fields and keep accepting options of earlier sizes, leaving appended fields as defaults.
+/// The earliest size ends at `direction`. `direction` is one of [`AsdfDirection`], and `alpha` is
+/// one of [`AsdfAlpha`] used for RGBA pixels.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct AsdfOptions {
+    pub size: usize,
+    pub mode: AsdfMode,
+    pub direction: u32,
+    pub alpha: u32,
+}
+
+/// Returns default options, same as [`Options::default()`].
+#[no_mangle]
+pub extern "C" fn asdf_options_default() -> AsdfOptions {
+    AsdfOptions {
+        size: size_of::<AsdfOptions>(),
+        mode: AsdfMode {
+            kind: AsdfModeKind::Brightness as u32,
+            color: AsdfColor::from(&PColor::default()),
+            threshold: DEFAULT_BRIGHTNESS,
+        },
+        direction: AsdfDirection::Both as u32,
+        alpha: AsdfAlpha::Ignore as u32,
+    }
+}
+
+/// Sorts pixels of a buffer in place with options.
+///
+/// Rows are `stride` bytes apart, and `stride` must be at least `width` times bytes of a pixel.
+/// `format` is one of [`AsdfFormat`]. `options` may be null to sort with default options.
+///
+/// # Safety
+///
+/// `pixels` must point to `stride * height` bytes writable, and `options` must be null or point
+/// to options of `size` bytes readable.
+#[no_mangle]
+pub unsafe extern "C" fn asdf_sort(
+    pixels: *mut u8,
+    width: u32,
+    height: u32,
+    stride: usize,
+    format: u32,
+    options: *const AsdfOptions,
+) -> AsdfStatus {
+    if pixels.is_null() {
+        return AsdfStatus::NullPointer;
+    }
+
+    let format = match AsdfFormat::try_from(format) {
+        Ok(format) => format,
+        Err(status) => return status,
+    };
+    let channels = match format {
+        AsdfFormat::Rgb => 3,
+        AsdfFormat::Rgba => 4,
+    };
+    let len = match (width as usize).checked_mul(channels) {
+        Some(len) if len <= stride => len,
+        _ => return AsdfStatus::InvalidDimensions,
+    };
+    let size = match stride.checked_mul(height as usize) {
+        Some(size) if size <= isize::MAX as usize => size,
+        _ => return AsdfStatus::InvalidDimensions,
+    };
+    if width == 0 || height == 0 {
+        return AsdfStatus::Ok;
+    }
+
+    let options = match read_options(options).map(|options| Options::try_from(&options)) {
+        Some(Ok(options)) => options,
+        Some(Err(status)) => return status,
+        None => Options::default(),
+    };
+    let buf = std::slice::from_raw_parts_mut(pixels, size);
+
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let packed = buf
+            .chunks(stride)
+            .flat_map(|row| &row[..len])
+            .copied()
+            .collect();
+        let sorted = match format {
+            AsdfFormat::Rgb => {
+                let mut image = RgbImage::from_raw(width, height, packed).unwrap();
+                sort_with_options(&mut image, &options);
+                image.into_raw()
+            }
+            AsdfFormat::Rgba => {
+                let mut image = RgbaImage::from_raw(width, height, packed).unwrap();
+                sort_rgba(&mut image, &options);
+                image.into_raw()
+            }
+        };
+
+        for (row, sorted) in buf.chunks_mut(stride).zip(sorted.chunks_exact(len)) {
+            row[..len].copy_from_slice(sorted);
+        }
+    }));
+
+    match result {
+        Ok(_) => AsdfStatus::Ok,
+        Err(_) => AsdfStatus::Panic,
+    }
+}
+
+/// Reads options of its `size` bytes over default options, or `None` if null.
+///
+/// # Safety
+///
+/// `options` must be null or point to options of `size` bytes readable.
+unsafe fn read_options(options: *const AsdfOptions) -> Option<AsdfOptions> {
+    if options.is_null() {
+        return None;
+    }
+
+    let mut read = asdf_options_default();
+    read.size = std::ptr::addr_of!((*options).size).read();
+    if is_known_size(read.size) {
+        // NOTE: Only fields within the size are read, so earlier options aren't overrun
+        let dst = &mut read as *mut AsdfOptions as *mut u8;
+        std::ptr::copy_nonoverlapping(options as *const u8, dst, read.size);
+    }
+    Some(read)
+}
+
+/// Checks if the size is of options of a version, the current one or an earlier one.
+fn is_known_size(size: usize) -> bool {
+    // NOTE: `alpha` is appended to `size`, `mode` and `direction` of the earliest options
+    let earliest = size_of::<usize>() + size_of::<AsdfMode>() + size_of::<u32>();
+    size == earliest || size == size_of::<AsdfOptions>()
+}
+
+/// Returns a static message of the status, one of [`AsdfStatus`], as a null-terminated string.
+#[no_mangle]
+pub extern "C" fn asdf_status_message(status: u32) -> *const c_char {
+    let message: &'static [u8] = match status {
+        0 => b"ok\0",
+        1 => b"null pointer\0",
+        2 => b"invalid dimensions\0",
+        3 => b"sorting failed\0",
+        4 => b"invalid argument\0",
+        _ => b"unknown status\0",
+    };
+    message.as_ptr() as *const c_char
+}
+
+impl TryFrom<u32> for AsdfFormat {
+    type Error = AsdfStatus;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(AsdfFormat::Rgb),
+            1 => Ok(AsdfFormat::Rgba),
+            _ => Err(AsdfStatus::InvalidArgument),
+        }
+    }
+}
+
+impl TryFrom<u32> for AsdfModeKind {
+    type Error = AsdfStatus;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(AsdfModeKind::Black),
+            1 => Ok(AsdfModeKind::Brightness),
+            2 => Ok(AsdfModeKind::White),
+            3 => Ok(AsdfModeKind::Edge),
+            _ => Err(AsdfStatus::InvalidArgument),
+        }
+    }
+}
+
+impl TryFrom<u32> for AsdfDirection {
+    type Error = AsdfStatus;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(AsdfDirection::Both),
+            1 => Ok(AsdfDirection::Column),
+            2 => Ok(AsdfDirection::Row),
+            _ => Err(AsdfStatus::InvalidArgument),
+        }
+    }
+}
+
+impl TryFrom<u32> for AsdfAlpha {
+    type Error = AsdfStatus;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(AsdfAlpha::Ignore),
+            1 => Ok(AsdfAlpha::Break),
+            2 => Ok(AsdfAlpha::Exclude),
+            3 => Ok(AsdfAlpha::Premultiply),
+            _ => Err(AsdfStatus::InvalidArgument),
+        }
+    }
+}
+
+impl From<&PColor> for AsdfColor {
+    fn from(color: &PColor) -> Self {
+        Self {
+            red: color.red,
+            green: color.green,
+            blue: color.blue,
+            alpha: color.alpha,
+        }
+    }
+}
+
+impl From<&AsdfColor> for PColor {
+    fn from(color: &AsdfColor) -> Self {
+        PColor::new(color.red, color.green, color.blue).with_alpha(color.alpha)
+    }
+}
+
+impl TryFrom<&AsdfOptions> for Options {
+    type Error = AsdfStatus;
+
+    fn try_from(options: &AsdfOptions) -> Result<Self, Self::Error> {
+        if !is_known_size(options.size) {
+            return Err(AsdfStatus::InvalidArgument);
+        }
+
+        let AsdfMode {
+            kind,
+            color,
+            threshold,
+        } = &options.mode;
+        let mode = match AsdfModeKind::try_from(*kind)? {
+            AsdfModeKind::Black => Mode::Black(color.into()),
+            AsdfModeKind::Brightness => Mode::Brightness(*threshold),
+            AsdfModeKind::White => Mode::White(color.into()),
+            AsdfModeKind::Edge => Mode::Edge(*threshold),
+        };
+        let direction = match AsdfDirection::try_from(options.direction)? {
+            AsdfDirection::Both => Direction::Both,
+            AsdfDirection::Column => Direction::Column,
+            AsdfDirection::Row => Direction::Row,
+        };
+        let alpha = if options.size < size_of::<AsdfOptions>() {
+            Alpha::default()
+        } else {
+            match AsdfAlpha::try_from(options.alpha)? {
+                AsdfAlpha::Ignore => Alpha::Ignore,
+                AsdfAlpha::Break => Alpha::Break,
+                AsdfAlpha::Exclude => Alpha::Exclude,
+                AsdfAlpha::Premultiply => Alpha::Premultiply,
+            }
+        };
+
+        Ok(Options {
+            mode,
+            direction,
+            alpha,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::Rgba;
+    use std::ffi::CStr;
+
+    fn load() -> RgbImage {
+        image::open("tests/p1.bmp").unwrap().to_rgb8()
+    }
+
+    #[test]
+    fn test_asdf_options_default() {
+        assert_eq!(
+            Options::default(),
+            Options::try_from(&asdf_options_default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_asdf_options_into() {
+        let mut options = asdf_options_default();
+        options.mode.kind = AsdfModeKind::White as u32;
+        options.mode.color = AsdfColor {
+            red: 1,
+            green: 2,
+            blue: 3,
+            alpha: 255,
+        };
+        options.direction = AsdfDirection::Row as u32;
+        options.alpha = AsdfAlpha::Exclude as u32;
+
+        let expected = Options {
+            mode: Mode::White(PColor::new(1, 2, 3)),
+            direction: Direction::Row,
+            alpha: Alpha::Exclude,
+            ..Default::default()
+        };
+        assert_eq!(expected, Options::try_from(&options).unwrap());
+    }
+
+    #[test]
+    fn test_asdf_sort_rgb() {
+        let mut options = asdf_options_default();
+        options.mode.kind = AsdfModeKind::Black as u32;
+        options.mode.color = AsdfColor::from(&PColor::new(11, 220, 0));
+
+        let mut expected = load();
+        sort_with_options(&mut expected, &Options::try_from(&options).unwrap());
+
+        let mut actual = load();
+        let (width, height) = actual.dimensions();
+        let status = unsafe {
+            asdf_sort(
+                actual.as_mut_ptr(),
+                width,
+                height,
+                width as usize * 3,
+                AsdfFormat::Rgb as u32,
+                &options,
+            )
+        };
+        assert_eq!(AsdfStatus::Ok, status);
+        assert!(expected == actual);
+    }
+
+    #[test]
+    fn test_asdf_sort_rgba_stride() {
+        // NOTE: Alpha values are marked by positions, and some pixels are transparent
+        let image = load();
+        let (width, height) = image.dimensions();
+        let image = RgbaImage::from_fn(width, height, |x, y| {
+            let [r, g, b] = image.get_pixel(x, y).0;
+            Rgba([r, g, b, (x % 256) as u8])
+        });
+        let mut options = asdf_options_default();
+        options.alpha = AsdfAlpha::Break as u32;
+
+        let mut expected = image.clone();
+        sort_rgba(&mut expected, &Options::try_from(&options).unwrap());
+
+        // NOTE: Rows are padded with 5 bytes
+        let stride = width as usize * 4 + 5;
+        let mut pixels = vec![7; stride * height as usize];
+        for (row, rgba) in pixels.chunks_mut(stride).zip(image.rows()) {
+            for (pixel, rgba) in row.chunks_exact_mut(4).zip(rgba) {
+                pixel.copy_from_slice(&rgba.0);
+            }
+        }
+
+        let status = unsafe {
+            asdf_sort(
+                pixels.as_mut_ptr(),
+                width,
+                height,
+                stride,
+                AsdfFormat::Rgba as u32,
+                &options,
+            )
+        };
+        assert_eq!(AsdfStatus::Ok, status);
+
+        for (y, row) in pixels.chunks(stride).enumerate() {
+            for (x, pixel) in row.chunks_exact(4).take(width as usize).enumerate() {
+                let expected = expected.get_pixel(x as u32, y as u32).0;
+                assert_eq!(expected, pixel, "alpha moves along");
+            }
+            assert_eq!([7; 5], row[width as usize * 4..], "padding");
+        }
+        assert!(expected != image, "sorted");
+    }
+
+    #[test]
+    fn test_asdf_sort_errors() {
+        let mut pixels = vec![0; 12];
+        let status = unsafe {
+            asdf_sort(
+                std::ptr::null_mut(),
+                2,
+                2,
+                6,
+                AsdfFormat::Rgb as u32,
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(AsdfStatus::NullPointer, status);
+
+        let status = unsafe {
+            asdf_sort(
+                pixels.as_mut_ptr(),
+                2,
+                2,
+                5,
+                AsdfFormat::Rgb as u32,
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(AsdfStatus::InvalidDimensions, status, "short stride");
+
+        let status = unsafe {
+            asdf_sort(
+                pixels.as_mut_ptr(),
+                u32::MAX,
+                2,
+                usize::MAX,
+                AsdfFormat::Rgba as u32,
+                std::ptr::null(),
+            )
+        };
+        assert_eq!(AsdfStatus::InvalidDimensions, status, "overflow");
+    }
+
+    #[test]
+    fn test_asdf_sort_invalid_arguments() {
+        let mut pixels = vec![0; 12];
+        let mut sort = |format: u32, options: &AsdfOptions| unsafe {
+            asdf_sort(pixels.as_mut_ptr(), 2, 2, 6, format, options)
+        };
+        let default = asdf_options_default();
+        assert_eq!(AsdfStatus::Ok, sort(AsdfFormat::Rgb as u32, &default));
+        assert_eq!(AsdfStatus::InvalidArgument, sort(2, &default), "format");
+
+        let mut options = default;
+        options.mode.kind = 4;
+        let status = sort(AsdfFormat::Rgb as u32, &options);
+        assert_eq!(AsdfStatus::InvalidArgument, status, "mode kind");
+
+        let mut options = default;
+        options.direction = u32::MAX;
+        let status = sort(AsdfFormat::Rgb as u32, &options);
+        assert_eq!(AsdfStatus::InvalidArgument, status, "direction");
+
+        let mut options = default;
+        options.alpha = 4;
+        let status = sort(AsdfFormat::Rgb as u32, &options);
+        assert_eq!(AsdfStatus::InvalidArgument, status, "alpha");
+
+        let mut options = default;
+        options.size -= 1;
+        let status = sort(AsdfFormat::Rgb as u32, &options);
+        assert_eq!(AsdfStatus::InvalidArgument, status, "size");
+    }
+
+    #[test]
+    fn test_asdf_sort_earlier_options() {
+        /// Options of the earliest version without `alpha`.
+        #[repr(C)]
+        struct Earliest {
+            size: usize,
+            mode: AsdfMode,
+            direction: u32,
+        }
+
+        let default = asdf_options_default();
+        let earliest = Earliest {
+            size: size_of::<Earliest>(),
+            mode: default.mode,
+            direction: AsdfDirection::Row as u32,
+        };
+        let options = unsafe { read_options(&earliest as *const Earliest as *const AsdfOptions) };
+        let expected = Options {
+            direction: Direction::Row,
+            ..Default::default()
+        };
+        assert_eq!(Ok(expected), Options::try_from(&options.unwrap()));
+
+        let mut pixels = vec![0; 16];
+        let status = unsafe {
+            asdf_sort(
+                pixels.as_mut_ptr(),
+                2,
+                2,
+                8,
+                AsdfFormat::Rgba as u32,
+                &earliest as *const Earliest as *const AsdfOptions,
+            )
+        };
+        assert_eq!(AsdfStatus::Ok, status);
+
+        let options = AsdfOptions {
+            size: size_of::<Earliest>(),
+            alpha: u32::MAX,
+            ..default
+        };
+        let options = Options::try_from(&options).unwrap();
+        assert_eq!(Alpha::Ignore, options.alpha, "appended fields as defaults");
+    }
+
+    #[test]
+    fn test_asdf_status_message() {
+        let message =
+            unsafe { CStr::from_ptr(asdf_status_message(AsdfStatus::NullPointer as u32)) };
+        assert_eq!("null pointer", message.to_str().unwrap());
+
+        let message = unsafe { CStr::from_ptr(asdf_status_message(u32::MAX)) };
+        assert_eq!("unknown status", message.to_str().unwrap());
+    }
+}
@@ -1,3 +1,66 @@
+use once_cell::sync::Lazy;
+
+/// Lookup table to decode sRGB-encoded values to linear light `0.0` to `1.0`.
+static SRGB_TO_LINEAR: Lazy<[f32; 256]> = Lazy::new(|| {
+    let mut table = [0.0; 256];
+    for (i, value) in table.iter_mut().enumerate() {
+        *value = srgb_to_linear(i as u8);
+    }
+    table
+});
+
+/// Decodes an sRGB-encoded value to linear light `0.0` to `1.0`.
+pub(crate) fn srgb_to_linear(value: u8) -> f32 {
+    let c = value as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts linear light `(r, g, b)` to OKLab `(L, a, b)`.
+pub(crate) fn oklab_of_linear((r, g, b): (f32, f32, f32)) -> (f32, f32, f32) {
+    let l = (0.412_221_46 * r + 0.536_332_54 * g + 0.051_445_995 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+
+    (
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    )
+}
+
+/// Converts linear light `(r, g, b)` to CIELAB `(L*, a*, b*)` under D65.
+pub(crate) fn lab_of_linear((r, g, b): (f32, f32, f32)) -> (f32, f32, f32) {
+    let x = (0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b) / 0.950_47;
+    let y = 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b;
+    let z = (0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b) / 1.088_83;
+
+    let f = |t: f32| {
+        const DELTA: f32 = 6.0 / 29.0;
+        if t > DELTA * DELTA * DELTA {
+            t.cbrt()
+        } else {
+            t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+
+    (116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+}
+
+/// Converts CIELAB `(L*, a*, b*)` to CIE LCh `(L*, C*, h)`.
+pub(crate) fn lch_of_lab((l, a, b): (f32, f32, f32)) -> (f32, f32, f32) {
+    let h = b.atan2(a).to_degrees();
+    (
+        l,
+        (a * a + b * b).sqrt(),
+        if h < 0.0 { h + 360.0 } else { h },
+    )
+}
+
 /// Representation of a color with Processing compatible
 #[derive(Clone, Eq, PartialEq)]
 pub struct PColor {
@@ -46,6 +109,12 @@ impl From<image::Rgb<u8>> for PColor {
     }
 }
 
+impl From<image::Rgba<u8>> for PColor {
+    fn from(c: image::Rgba<u8>) -> Self {
+        PColor::new(c.0[0], c.0[1], c.0[2]).with_alpha(c.0[3])
+    }
+}
+
 impl From<i32> for PColor {
     fn from(c: i32) -> Self {
         PColor::from_raw(c)
@@ -140,6 +209,56 @@ impl PColor {
     pub fn brightness(&self) -> u8 {
         self.red.max(self.green).max(self.blue)
     }
+
+    /// Decodes sRGB-encoded channels to linear light `(r, g, b)` between `0.0` to `1.0`.
+    pub fn linear_rgb(&self) -> (f32, f32, f32) {
+        (
+            SRGB_TO_LINEAR[self.red as usize],
+            SRGB_TO_LINEAR[self.green as usize],
+            SRGB_TO_LINEAR[self.blue as usize],
+        )
+    }
+
+    /// Converts to OKLab `(L, a, b)`, where lightness `L` is between `0.0` to `1.0`.
+    ///
+    /// Ref. https://bottosson.github.io/posts/oklab/
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use asdf_pixel_sort::PColor;
+    /// let (l, _, _) = PColor::new(255, 255, 255).oklab();
+    /// assert!((l - 1.0).abs() < 1e-3);
+    /// ```
+    pub fn oklab(&self) -> (f32, f32, f32) {
+        oklab_of_linear(self.linear_rgb())
+    }
+
+    /// Converts to CIELAB `(L*, a*, b*)` under D65, where `L*` is between `0.0` to `100.0`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use asdf_pixel_sort::PColor;
+    /// let (l, _, _) = PColor::new(255, 255, 255).lab();
+    /// assert!((l - 100.0).abs() < 1e-2);
+    /// ```
+    pub fn lab(&self) -> (f32, f32, f32) {
+        lab_of_linear(self.linear_rgb())
+    }
+
+    /// Converts to CIE LCh `(L*, C*, h)` under D65, where hue `h` is in degrees `0.0` to `360.0`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use asdf_pixel_sort::PColor;
+    /// let (_, c, _) = PColor::new(128, 128, 128).lch();
+    /// assert!(c < 1e-2);
+    /// ```
+    pub fn lch(&self) -> (f32, f32, f32) {
+        lch_of_lab(self.lab())
+    }
 }
 
 #[cfg(test)]
@@ -187,6 +306,12 @@ mod tests {
         assert_eq!(expected, PColor::from_raw(-13000000));
     }
 
+    #[test]
+    fn test_pcolor_from_rgba() {
+        let expected = PColor::new(11, 220, 0).with_alpha(128);
+        assert_eq!(expected, PColor::from(image::Rgba([11, 220, 0, 128])));
+    }
+
     #[test]
     fn test_pcolor_with_alpha() {
         let expected = PColor {
@@ -216,6 +341,58 @@ mod tests {
         assert_eq!(expected, PColor::new(0, 0, 0).brightness());
     }
 
+    #[test]
+    fn test_srgb_to_linear() {
+        assert_eq!(0.0, srgb_to_linear(0));
+        assert_eq!(1.0, srgb_to_linear(255));
+        assert!((srgb_to_linear(10) - 0.003_035).abs() < 1e-6);
+        assert!((srgb_to_linear(128) - 0.215_861).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_pcolor_linear_rgb() {
+        let (r, g, b) = PColor::new(0, 128, 255).linear_rgb();
+        assert_eq!(0.0, r);
+        assert!((g - 0.215_861).abs() < 1e-6);
+        assert_eq!(1.0, b);
+    }
+
+    #[test]
+    fn test_pcolor_oklab() {
+        let (l, a, b) = PColor::new(0, 0, 0).oklab();
+        assert!(l.abs() < 1e-4 && a.abs() < 1e-4 && b.abs() < 1e-4);
+
+        let (l, a, b) = PColor::new(255, 255, 255).oklab();
+        assert!((l - 1.0).abs() < 1e-3 && a.abs() < 1e-3 && b.abs() < 1e-3);
+
+        let (l, a, b) = PColor::new(255, 0, 0).oklab();
+        assert!((l - 0.628).abs() < 1e-3);
+        assert!((a - 0.225).abs() < 1e-3);
+        assert!((b - 0.126).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_pcolor_lab() {
+        let (l, a, b) = PColor::new(0, 0, 0).lab();
+        assert!(l.abs() < 1e-3 && a.abs() < 1e-3 && b.abs() < 1e-3);
+
+        let (l, a, b) = PColor::new(255, 0, 0).lab();
+        assert!((l - 53.24).abs() < 1e-1);
+        assert!((a - 80.09).abs() < 1e-1);
+        assert!((b - 67.20).abs() < 1e-1);
+    }
+
+    #[test]
+    fn test_pcolor_lch() {
+        let (l, c, h) = PColor::new(255, 0, 0).lch();
+        assert!((l - 53.24).abs() < 1e-1);
+        assert!((c - 104.55).abs() < 1e-1);
+        assert!((h - 40.0).abs() < 1e-1);
+
+        let (_, _, h) = PColor::new(0, 0, 255).lch();
+        assert!((h - 306.28).abs() < 1e-1, "positive hue");
+    }
+
     #[test]
     fn test_pcolor_cmp() {
         let c1 = PColor::new(11, 220, 0);
@@ -0,0 +1,56 @@
+use image::{Rgb, RgbImage};
+
+/// Computes an edge map of the given image with Sobel operator.
+///
+/// Pixels of the map have the gradient magnitude inverted, so edges are dark and flat areas are
+/// bright. This lets the brightness finders detect intervals between edges.
+pub(crate) fn edge_map(image: &RgbImage) -> RgbImage {
+    let (width, height) = image.dimensions();
+    let luma = |x: i64, y: i64| {
+        let x = x.clamp(0, width as i64 - 1) as u32;
+        let y = y.clamp(0, height as i64 - 1) as u32;
+        let [r, g, b] = image.get_pixel(x, y).0;
+        (299 * r as i64 + 587 * g as i64 + 114 * b as i64) / 1000
+    };
+
+    RgbImage::from_fn(width, height, |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        let gx = luma(x + 1, y - 1) + 2 * luma(x + 1, y) + luma(x + 1, y + 1)
+            - luma(x - 1, y - 1)
+            - 2 * luma(x - 1, y)
+            - luma(x - 1, y + 1);
+        let gy = luma(x - 1, y + 1) + 2 * luma(x, y + 1) + luma(x + 1, y + 1)
+            - luma(x - 1, y - 1)
+            - 2 * luma(x, y - 1)
+            - luma(x + 1, y - 1);
+        let magnitude = (((gx * gx + gy * gy) as f64).sqrt() / 4.0).min(255.0) as u8;
+        let value = 255 - magnitude;
+        Rgb([value, value, value])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edge_map_flat() {
+        let image = RgbImage::from_pixel(4, 3, Rgb([128, 64, 32]));
+        let map = edge_map(&image);
+        assert_eq!((4, 3), map.dimensions());
+        assert!(map.pixels().all(|p| *p == Rgb([255, 255, 255])));
+    }
+
+    #[test]
+    fn test_edge_map_step() {
+        let image = RgbImage::from_fn(6, 1, |x, _| {
+            let value = if x < 3 { 0 } else { 255 };
+            Rgb([value, value, value])
+        });
+        let map = edge_map(&image);
+        assert_eq!(Rgb([255, 255, 255]), *map.get_pixel(0, 0));
+        assert_eq!(Rgb([0, 0, 0]), *map.get_pixel(2, 0));
+        assert_eq!(Rgb([0, 0, 0]), *map.get_pixel(3, 0));
+        assert_eq!(Rgb([255, 255, 255]), *map.get_pixel(5, 0));
+    }
+}
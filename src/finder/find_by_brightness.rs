@@ -1,19 +1,26 @@
-use image::RgbImage;
-
-use crate::PColor;
+use super::{pixel_at, scan};
+use crate::{threshold::Threshold, PColor};
 
 pub(crate) fn get_first_bright_x(
-    buf: &RgbImage,
+    row: &[u8],
     x_start: u32,
     y: u32,
-    brightness: u8,
+    brightness: &impl Threshold<u8>,
 ) -> Option<u32> {
-    let width = buf.width();
+    let width = (row.len() / 3) as u32;
+
+    if let Some(brightness) = brightness.uniform() {
+        if x_start < width {
+            let rest = &row[x_start as usize * 3..];
+            return scan::position(rest, [brightness; 3], scan::bright).map(|i| x_start + i as u32);
+        }
+    }
+
     let mut x = x_start;
 
     while x < width {
-        let pixel = buf.get_pixel(x, y);
-        if brightness <= PColor::from(*pixel).brightness() {
+        let pixel = pixel_at(row, x);
+        if brightness.at(x, y) <= PColor::from(pixel).brightness() {
             break; // found bright pixel
         }
 
@@ -27,13 +34,29 @@ pub(crate) fn get_first_bright_x(
     Some(x)
 }
 
-pub(crate) fn get_next_dark_x(buf: &RgbImage, x_start: u32, y: u32, brightness: u8) -> u32 {
-    let width = buf.width();
+pub(crate) fn get_next_dark_x(
+    row: &[u8],
+    x_start: u32,
+    y: u32,
+    brightness: &impl Threshold<u8>,
+) -> u32 {
+    let width = (row.len() / 3) as u32;
+
+    if let Some(brightness) = brightness.uniform() {
+        if x_start < width {
+            let rest = &row[(x_start as usize + 1) * 3..];
+            return match scan::position(rest, [brightness; 3], scan::dark) {
+                Some(i) => x_start + i as u32,
+                None => width - 1,
+            };
+        }
+    }
+
     let mut x = x_start + 1;
 
     while x < width {
-        let pixel = buf.get_pixel(x, y);
-        if PColor::from(*pixel).brightness() <= brightness {
+        let pixel = pixel_at(row, x);
+        if PColor::from(pixel).brightness() <= brightness.at(x, y) {
             break; // found dark pixel
         }
 
@@ -47,61 +70,10 @@ pub(crate) fn get_next_dark_x(buf: &RgbImage, x_start: u32, y: u32, brightness:
     x - 1
 }
 
-pub(crate) fn get_first_bright_y(
-    buf: &RgbImage,
-    x: u32,
-    y_start: u32,
-    brightness: u8,
-) -> Option<u32> {
-    let height = buf.height();
-    let mut y = y_start;
-
-    if y < height {
-        loop {
-            let pixel = buf.get_pixel(x, y);
-            if brightness <= PColor::from(*pixel).brightness() {
-                break; // found bright pixel
-            }
-
-            y += 1;
-
-            if height <= y {
-                return None;
-            }
-        }
-    }
-
-    Some(y)
-}
-
-pub(crate) fn get_next_dark_y(buf: &RgbImage, x: u32, y_start: u32, brightness: u8) -> u32 {
-    let height = buf.height();
-    let mut y = y_start + 1;
-
-    if y < height {
-        loop {
-            let pixel = buf.get_pixel(x, y);
-            if PColor::from(*pixel).brightness() <= brightness {
-                break; // found dark pixel
-            }
-
-            y += 1;
-
-            if height <= y {
-                return height - 1;
-            }
-        }
-    }
-
-    y - 1
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use image::ImageBuffer;
-
     static BRIGHTNESS: u8 = 60;
 
     #[test]
@@ -114,22 +86,22 @@ mod tests {
             8, 8, 255, //
             4, 4, 4, //
         ];
-        let buf: RgbImage = ImageBuffer::from_raw(6, 1, Vec::from(&data[..])).unwrap();
+        let row = &data[..];
 
-        let actual = get_first_bright_x(&buf, 0, 0, BRIGHTNESS);
+        let actual = get_first_bright_x(row, 0, 0, &BRIGHTNESS);
         assert_eq!(actual, Some(2));
 
-        let actual = get_first_bright_x(&buf, 2, 0, BRIGHTNESS);
+        let actual = get_first_bright_x(row, 2, 0, &BRIGHTNESS);
         assert_eq!(actual, Some(2), "same with start position");
 
-        let actual = get_first_bright_x(&buf, 3, 0, BRIGHTNESS);
+        let actual = get_first_bright_x(row, 3, 0, &BRIGHTNESS);
         assert_eq!(actual, Some(4), "non-zero start");
 
-        let actual = get_first_bright_x(&buf, 5, 0, BRIGHTNESS);
+        let actual = get_first_bright_x(row, 5, 0, &BRIGHTNESS);
         assert_eq!(actual, None, "not found");
 
         // NOTE: This spec is wiered, but same with original
-        let actual = get_first_bright_x(&buf, 6, 0, BRIGHTNESS);
+        let actual = get_first_bright_x(row, 6, 0, &BRIGHTNESS);
         assert_eq!(actual, Some(6), "out of bounds");
     }
 
@@ -144,70 +116,18 @@ mod tests {
             192, 192, 192, //
             192, 192, 192, //
         ];
-        let buf: RgbImage = ImageBuffer::from_raw(7, 1, Vec::from(&data[..])).unwrap();
+        let row = &data[..];
 
-        let actual = get_next_dark_x(&buf, 0, 0, BRIGHTNESS);
+        let actual = get_next_dark_x(row, 0, 0, &BRIGHTNESS);
         assert_eq!(actual, 1);
 
-        let actual = get_next_dark_x(&buf, 2, 0, BRIGHTNESS);
+        let actual = get_next_dark_x(row, 2, 0, &BRIGHTNESS);
         assert_eq!(actual, 3, "skip start position even if it's dark");
 
-        let actual = get_next_dark_x(&buf, 4, 0, BRIGHTNESS);
+        let actual = get_next_dark_x(row, 4, 0, &BRIGHTNESS);
         assert_eq!(actual, 6, "not found");
 
-        let actual = get_next_dark_x(&buf, 6, 0, BRIGHTNESS);
+        let actual = get_next_dark_x(row, 6, 0, &BRIGHTNESS);
         assert_eq!(actual, 6, "out of bounds");
     }
-
-    #[test]
-    fn test_get_first_bright_y() {
-        let data = [
-            0, 0, 4, //
-            0, 8, 12, //
-            0, 60, 0, //
-            16, 16, 16, //
-            8, 8, 8, //
-            4, 4, 4, //
-        ];
-        let buf: RgbImage = ImageBuffer::from_raw(1, 6, Vec::from(&data[..])).unwrap();
-
-        let actual = get_first_bright_y(&buf, 0, 0, BRIGHTNESS);
-        assert_eq!(actual, Some(2));
-
-        let actual = get_first_bright_y(&buf, 0, 2, BRIGHTNESS);
-        assert_eq!(actual, Some(2), "same with initial position");
-
-        let actual = get_first_bright_y(&buf, 0, 3, BRIGHTNESS);
-        assert_eq!(actual, None, "not found");
-
-        // NOTE: This spec is wiered, but same with original
-        let actual = get_first_bright_y(&buf, 0, 6, BRIGHTNESS);
-        assert_eq!(actual, Some(6), "out of bounds");
-    }
-
-    #[test]
-    fn test_get_next_dark_y() {
-        let data = [
-            255, 0, 255, //
-            0, 0, 192, //
-            0, 60, 0, //
-            61, 0, 0, //
-            8, 8, 8, //
-            192, 192, 192, //
-            192, 192, 192, //
-        ];
-        let buf: RgbImage = ImageBuffer::from_raw(1, 7, Vec::from(&data[..])).unwrap();
-
-        let actual = get_next_dark_y(&buf, 0, 0, BRIGHTNESS);
-        assert_eq!(1, actual);
-
-        let actual = get_next_dark_y(&buf, 0, 2, BRIGHTNESS);
-        assert_eq!(3, actual, "skip start position even if it's dark");
-
-        let actual = get_next_dark_y(&buf, 0, 5, BRIGHTNESS);
-        assert_eq!(6, actual, "not found");
-
-        let actual = get_next_dark_y(&buf, 0, 6, BRIGHTNESS);
-        assert_eq!(6, actual, "out of bounds");
-    }
 }
@@ -1,19 +1,27 @@
-use image::RgbImage;
-
-use crate::PColor;
+use super::{pixel_at, scan};
+use crate::{threshold::Threshold, PColor};
 
 pub(crate) fn get_first_not_white_x(
-    buf: &RgbImage,
+    row: &[u8],
     x_start: u32,
     y: u32,
-    white: &PColor,
+    white: &impl Threshold<PColor>,
 ) -> Option<u32> {
-    let width = buf.width();
+    let width = (row.len() / 3) as u32;
+
+    if let Some(white) = white.uniform().filter(|white| white.alpha == 255) {
+        if x_start < width {
+            let rest = &row[x_start as usize * 3..];
+            return scan::position(rest, [white.red, white.green, white.blue], scan::at_most)
+                .map(|i| x_start + i as u32);
+        }
+    }
+
     let mut x = x_start;
 
     while x < width {
-        let pixel = buf.get_pixel(x, y);
-        if *white >= (*pixel).into() {
+        let pixel = pixel_at(row, x);
+        if white.at(x, y) >= pixel.into() {
             break; // found non-white pixel
         }
 
@@ -27,13 +35,30 @@ pub(crate) fn get_first_not_white_x(
     Some(x)
 }
 
-pub(crate) fn get_next_white_x(buf: &RgbImage, x_start: u32, y: u32, white: &PColor) -> u32 {
-    let width = buf.width();
+pub(crate) fn get_next_white_x(
+    row: &[u8],
+    x_start: u32,
+    y: u32,
+    white: &impl Threshold<PColor>,
+) -> u32 {
+    let width = (row.len() / 3) as u32;
+
+    if let Some(white) = white.uniform().filter(|white| white.alpha == 255) {
+        if x_start < width {
+            let rest = &row[(x_start as usize + 1) * 3..];
+            return match scan::position(rest, [white.red, white.green, white.blue], scan::at_least)
+            {
+                Some(i) => x_start + i as u32,
+                None => width - 1,
+            };
+        }
+    }
+
     let mut x = x_start + 1;
 
     while x < width {
-        let pixel = buf.get_pixel(x, y);
-        if *white <= (*pixel).into() {
+        let pixel = pixel_at(row, x);
+        if white.at(x, y) <= pixel.into() {
             break; // found white pixel
         }
 
@@ -47,59 +72,10 @@ pub(crate) fn get_next_white_x(buf: &RgbImage, x_start: u32, y: u32, white: &PCo
     x - 1
 }
 
-pub(crate) fn get_first_not_white_y(
-    buf: &RgbImage,
-    x: u32,
-    y_start: u32,
-    white: &PColor,
-) -> Option<u32> {
-    let mut y = y_start;
-
-    if y < buf.height() {
-        loop {
-            let pixel = buf.get_pixel(x, y);
-            if *white >= (*pixel).into() {
-                break; // found non-white pixel
-            }
-
-            y += 1;
-
-            if buf.height() <= y {
-                return None;
-            }
-        }
-    }
-
-    Some(y)
-}
-
-pub(crate) fn get_next_white_y(buf: &RgbImage, x: u32, y_start: u32, white: &PColor) -> u32 {
-    let height = buf.height();
-    let mut y = y_start + 1;
-
-    if y < height {
-        loop {
-            let pixel = buf.get_pixel(x, y);
-            if *white <= (*pixel).into() {
-                break; // found white pixel
-            }
-
-            y += 1;
-
-            if height <= y {
-                return height - 1;
-            }
-        }
-    }
-
-    y - 1
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use image::ImageBuffer;
     use once_cell::sync::Lazy;
 
     static WHITE: Lazy<PColor> = Lazy::new(|| PColor::new(192, 192, 192));
@@ -114,19 +90,19 @@ mod tests {
             248, 248, 248, //
             255, 255, 255, //
         ];
-        let buf: RgbImage = ImageBuffer::from_raw(6, 1, Vec::from(&data[..])).unwrap();
+        let row = &data[..];
 
-        let actual = get_first_not_white_x(&buf, 0, 0, &WHITE);
+        let actual = get_first_not_white_x(row, 0, 0, &*WHITE);
         assert_eq!(Some(2), actual);
 
-        let actual = get_first_not_white_x(&buf, 3, 0, &WHITE);
+        let actual = get_first_not_white_x(row, 3, 0, &*WHITE);
         assert_eq!(Some(3), actual, "same with start position");
 
-        let actual = get_first_not_white_x(&buf, 4, 0, &WHITE);
+        let actual = get_first_not_white_x(row, 4, 0, &*WHITE);
         assert_eq!(None, actual, "not found");
 
         // NOTE: This spec is wiered, but same with original
-        let actual = get_first_not_white_x(&buf, 6, 0, &WHITE);
+        let actual = get_first_not_white_x(row, 6, 0, &*WHITE);
         assert_eq!(Some(6), actual, "out of bounds");
     }
 
@@ -141,70 +117,18 @@ mod tests {
             4, 4, 4, //
             4, 4, 4, //
         ];
-        let buf: RgbImage = ImageBuffer::from_raw(7, 1, Vec::from(&data[..])).unwrap();
-
-        let actual = get_next_white_x(&buf, 0, 0, &WHITE);
-        assert_eq!(1, actual);
-
-        let actual = get_next_white_x(&buf, 2, 0, &WHITE);
-        assert_eq!(3, actual, "skip start position even if it's white");
-
-        let actual = get_next_white_x(&buf, 5, 0, &WHITE);
-        assert_eq!(6, actual, "not found");
-
-        let actual = get_next_white_x(&buf, 6, 0, &WHITE);
-        assert_eq!(6, actual, "out of bounds");
-    }
-
-    #[test]
-    fn test_get_first_not_white_y() {
-        let data = [
-            255, 255, 255, //
-            193, 193, 193, //
-            192, 192, 192, //
-            191, 191, 191, //
-            248, 248, 248, //
-            255, 255, 255, //
-        ];
-        let buf: RgbImage = ImageBuffer::from_raw(1, 6, Vec::from(&data[..])).unwrap();
-
-        let actual = get_first_not_white_y(&buf, 0, 0, &WHITE);
-        assert_eq!(Some(2), actual);
-
-        let actual = get_first_not_white_y(&buf, 0, 3, &WHITE);
-        assert_eq!(Some(3), actual, "same with start position");
-
-        let actual = get_first_not_white_y(&buf, 0, 4, &WHITE);
-        assert_eq!(None, actual, "not found");
-
-        // NOTE: This spec is wiered, but same with original
-        let actual = get_first_not_white_y(&buf, 0, 6, &WHITE);
-        assert_eq!(Some(6), actual, "out of bounds");
-    }
-
-    #[test]
-    fn test_get_next_white_y() {
-        let data = [
-            0, 0, 0, //
-            191, 191, 191, //
-            192, 192, 192, //
-            8, 8, 8, //
-            255, 255, 255, //
-            4, 4, 4, //
-            4, 4, 4, //
-        ];
-        let buf: RgbImage = ImageBuffer::from_raw(1, 7, Vec::from(&data[..])).unwrap();
+        let row = &data[..];
 
-        let actual = get_next_white_y(&buf, 0, 0, &WHITE);
+        let actual = get_next_white_x(row, 0, 0, &*WHITE);
         assert_eq!(1, actual);
 
-        let actual = get_next_white_y(&buf, 0, 2, &WHITE);
+        let actual = get_next_white_x(row, 2, 0, &*WHITE);
         assert_eq!(3, actual, "skip start position even if it's white");
 
-        let actual = get_next_white_y(&buf, 0, 5, &WHITE);
+        let actual = get_next_white_x(row, 5, 0, &*WHITE);
         assert_eq!(6, actual, "not found");
 
-        let actual = get_next_white_y(&buf, 0, 6, &WHITE);
+        let actual = get_next_white_x(row, 6, 0, &*WHITE);
         assert_eq!(6, actual, "out of bounds");
     }
 }
@@ -1,43 +1,73 @@
-use image::RgbImage;
+use image::{Pixel, Rgb};
 
-use crate::Mode;
+use crate::{threshold::Local, Mode, Variation};
 
 mod find_by_black;
 mod find_by_brightness;
 mod find_by_white;
+mod scan;
 
 use find_by_black::*;
 use find_by_brightness::*;
 use find_by_white::*;
 
-pub(crate) fn get_first_x(buf: &RgbImage, x_start: u32, y: u32, mode: &Mode) -> Option<u32> {
-    match mode {
-        Mode::Black(black) => get_first_not_black_x(buf, x_start, y, black),
-        Mode::Brightness(value) => get_first_bright_x(buf, x_start, y, *value),
-        Mode::White(white) => get_first_not_white_x(buf, x_start, y, white),
-    }
-}
+// NOTE: Finders scan `row`, RGB subpixels of the row `y`.
+// For `Mode::Edge`, `row` is expected to be a row of an edge map from `edge::edge_map()`.
 
-pub(crate) fn get_next_x(buf: &RgbImage, x_start: u32, y: u32, mode: &Mode) -> u32 {
+pub(crate) fn get_first_x(
+    row: &[u8],
+    x_start: u32,
+    y: u32,
+    mode: &Mode,
+    variation: &Variation,
+) -> Option<u32> {
     match mode {
-        Mode::Black(black) => get_next_black_x(buf, x_start, y, black),
-        Mode::Brightness(value) => get_next_dark_x(buf, x_start, y, *value),
-        Mode::White(white) => get_next_white_x(buf, x_start, y, white),
+        Mode::Black(black) => {
+            get_first_not_black_x(row, x_start, y, &Local::new(mode, black, variation, y))
+        }
+        Mode::Brightness(value) => {
+            get_first_bright_x(row, x_start, y, &Local::new(mode, value, variation, y))
+        }
+        Mode::White(white) => {
+            get_first_not_white_x(row, x_start, y, &Local::new(mode, white, variation, y))
+        }
+        Mode::Edge(value) => get_first_bright_x(
+            row,
+            x_start,
+            y,
+            &Local::new(mode, &(255 - value), variation, y),
+        ),
     }
 }
 
-pub(crate) fn get_first_y(buf: &RgbImage, x: u32, y_start: u32, mode: &Mode) -> Option<u32> {
+pub(crate) fn get_next_x(
+    row: &[u8],
+    x_start: u32,
+    y: u32,
+    mode: &Mode,
+    variation: &Variation,
+) -> u32 {
     match mode {
-        Mode::Black(black) => get_first_not_black_y(buf, x, y_start, black),
-        Mode::Brightness(value) => get_first_bright_y(buf, x, y_start, *value),
-        Mode::White(white) => get_first_not_white_y(buf, x, y_start, white),
+        Mode::Black(black) => {
+            get_next_black_x(row, x_start, y, &Local::new(mode, black, variation, y))
+        }
+        Mode::Brightness(value) => {
+            get_next_dark_x(row, x_start, y, &Local::new(mode, value, variation, y))
+        }
+        Mode::White(white) => {
+            get_next_white_x(row, x_start, y, &Local::new(mode, white, variation, y))
+        }
+        Mode::Edge(value) => get_next_dark_x(
+            row,
+            x_start,
+            y,
+            &Local::new(mode, &(255 - value), variation, y),
+        ),
     }
 }
 
-pub(crate) fn get_next_y(buf: &RgbImage, x: u32, y_start: u32, mode: &Mode) -> u32 {
-    match mode {
-        Mode::Black(black) => get_next_black_y(buf, x, y_start, black),
-        Mode::Brightness(value) => get_next_dark_y(buf, x, y_start, *value),
-        Mode::White(white) => get_next_white_y(buf, x, y_start, white),
-    }
+/// Returns the pixel at `x` in RGB subpixels of a row.
+fn pixel_at(row: &[u8], x: u32) -> Rgb<u8> {
+    let i = x as usize * 3;
+    *Rgb::from_slice(&row[i..i + 3])
 }
@@ -0,0 +1,181 @@
+use std::convert::TryInto;
+
+/// Number of bytes, 16 RGB pixels, compared at once.
+const CHUNK: usize = 48;
+
+/// Bit mask of the first channel of each pixel in a chunk.
+const PIXELS: u64 = 0x2492_4924_9249;
+
+/// Finds the first pixel in RGB subpixels satisfying `test` against the threshold.
+///
+/// `test` combines bit masks of subpixels greater than and equal to the threshold into a mask
+/// which has the bit of the first channel set for satisfying pixels.
+pub(crate) fn position(
+    row: &[u8],
+    threshold: [u8; 3],
+    test: impl Fn(u64, u64) -> u64,
+) -> Option<usize> {
+    let mut pattern = [0; CHUNK];
+    for value in pattern.chunks_exact_mut(3) {
+        value.copy_from_slice(&threshold);
+    }
+
+    let mut chunks = row.chunks_exact(CHUNK);
+    for (i, chunk) in (&mut chunks).enumerate() {
+        let (gt, eq) = compare(chunk.try_into().unwrap(), &pattern);
+        let found = test(gt, eq) & PIXELS;
+        if found != 0 {
+            return Some(i * CHUNK / 3 + found.trailing_zeros() as usize / 3);
+        }
+    }
+
+    let remainder = chunks.remainder();
+    let mut last = [0; CHUNK];
+    last[..remainder.len()].copy_from_slice(remainder);
+    let (gt, eq) = compare(&last, &pattern);
+    let found = test(gt, eq) & PIXELS & ((1 << remainder.len()) - 1);
+    if found != 0 {
+        return Some((row.len() - remainder.len()) / 3 + found.trailing_zeros() as usize / 3);
+    }
+
+    None
+}
+
+/// Tests the color is greater than or equal to the threshold in Processing's order.
+pub(crate) fn at_least(gt: u64, eq: u64) -> u64 {
+    gt | eq & (gt >> 1 | eq >> 1 & (gt >> 2 | eq >> 2))
+}
+
+/// Tests the color is less than or equal to the threshold in Processing's order.
+pub(crate) fn at_most(gt: u64, eq: u64) -> u64 {
+    at_least(!(gt | eq), eq)
+}
+
+/// Tests the brightness, the maximum of channels, is greater than or equal to the threshold.
+pub(crate) fn bright(gt: u64, eq: u64) -> u64 {
+    let ge = gt | eq;
+    ge | ge >> 1 | ge >> 2
+}
+
+/// Tests the brightness, the maximum of channels, is less than or equal to the threshold.
+pub(crate) fn dark(gt: u64, _eq: u64) -> u64 {
+    let le = !gt;
+    le & le >> 1 & le >> 2
+}
+
+/// Compares subpixels with the threshold into bit masks of greater ones and equal ones.
+#[cfg(target_arch = "x86_64")]
+fn compare(bytes: &[u8; CHUNK], threshold: &[u8; CHUNK]) -> (u64, u64) {
+    use std::arch::x86_64::*;
+
+    let (mut gt, mut eq) = (0, 0);
+    // SAFETY: SSE2 is a part of the x86_64 baseline, and loads are unaligned within arrays.
+    unsafe {
+        let bias = _mm_set1_epi8(i8::MIN);
+        for i in 0..CHUNK / 16 {
+            let a = _mm_loadu_si128(bytes.as_ptr().add(i * 16) as *const __m128i);
+            let b = _mm_loadu_si128(threshold.as_ptr().add(i * 16) as *const __m128i);
+            // NOTE: Unsigned comparison by flipping sign bits for signed comparison
+            let greater = _mm_cmpgt_epi8(_mm_xor_si128(a, bias), _mm_xor_si128(b, bias));
+            let equal = _mm_cmpeq_epi8(a, b);
+            gt |= (_mm_movemask_epi8(greater) as u16 as u64) << (i * 16);
+            eq |= (_mm_movemask_epi8(equal) as u16 as u64) << (i * 16);
+        }
+    }
+
+    (gt, eq)
+}
+
+/// Compares subpixels with the threshold into bit masks of greater ones and equal ones.
+#[cfg(not(target_arch = "x86_64"))]
+fn compare(bytes: &[u8; CHUNK], threshold: &[u8; CHUNK]) -> (u64, u64) {
+    compare_scalar(bytes, threshold)
+}
+
+#[cfg(any(test, not(target_arch = "x86_64")))]
+fn compare_scalar(bytes: &[u8; CHUNK], threshold: &[u8; CHUNK]) -> (u64, u64) {
+    let (mut gt, mut eq) = (0, 0);
+    for (i, (a, b)) in bytes.iter().zip(threshold.iter()).enumerate() {
+        gt |= ((a > b) as u64) << i;
+        eq |= ((a == b) as u64) << i;
+    }
+
+    (gt, eq)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::PColor;
+
+    fn pixels(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len * 3)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                // NOTE: Narrow range to hit equal channels often
+                (state >> 16) as u8 % 8 * 32
+            })
+            .collect()
+    }
+
+    fn naive(row: &[u8], test: impl Fn(&[u8]) -> bool) -> Option<usize> {
+        row.chunks_exact(3).position(test)
+    }
+
+    #[test]
+    fn test_compare() {
+        let bytes = pixels(16, 1).try_into().unwrap();
+        let threshold = pixels(16, 2).try_into().unwrap();
+        assert_eq!(
+            compare_scalar(&bytes, &threshold),
+            compare(&bytes, &threshold)
+        );
+
+        let bytes = [255; CHUNK];
+        assert_eq!((!0 >> 16, 0), compare(&bytes, &[0; CHUNK]), "unsigned");
+    }
+
+    #[test]
+    fn test_position_color() {
+        let threshold = [96, 128, 160];
+        let color = PColor::new(96, 128, 160);
+        for seed in 0..200 {
+            let row = pixels(seed as usize % 40, seed);
+            let of = |p: &[u8]| PColor::new(p[0], p[1], p[2]);
+            assert_eq!(
+                naive(&row, |p| color <= of(p)),
+                position(&row, threshold, at_least),
+                "at least, seed {}",
+                seed
+            );
+            assert_eq!(
+                naive(&row, |p| of(p) <= color),
+                position(&row, threshold, at_most),
+                "at most, seed {}",
+                seed
+            );
+        }
+    }
+
+    #[test]
+    fn test_position_brightness() {
+        for seed in 0..200 {
+            let row = pixels(seed as usize % 40, seed);
+            let brightness = |p: &[u8]| PColor::new(p[0], p[1], p[2]).brightness();
+            assert_eq!(
+                naive(&row, |p| 128 <= brightness(p)),
+                position(&row, [128; 3], bright),
+                "bright, seed {}",
+                seed
+            );
+            assert_eq!(
+                naive(&row, |p| brightness(p) <= 128),
+                position(&row, [128; 3], dark),
+                "dark, seed {}",
+                seed
+            );
+        }
+    }
+}
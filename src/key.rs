@@ -0,0 +1,240 @@
+use image::Rgb;
+
+use crate::{
+    color::{lab_of_linear, lch_of_lab, oklab_of_linear},
+    radix::{radix_sort, Entry},
+    transfer::{Decoder, RGB},
+    Key, PColor, TieBreak,
+};
+
+/// Sorting keys of pixels decoded by the transfer function if given.
+pub(crate) struct Keys<'a> {
+    key: &'a Key,
+    tie: &'a TieBreak,
+    decoder: Option<&'a Decoder>,
+    channels: [usize; 3],
+}
+
+impl<'a> Keys<'a> {
+    pub(crate) fn new(key: &'a Key, tie: &'a TieBreak, decoder: Option<&'a Decoder>) -> Self {
+        Self {
+            key,
+            tie,
+            decoder,
+            channels: RGB,
+        }
+    }
+
+    /// Returns keys of pixels of a channel plane, whose subpixels are all decoded as the channel.
+    pub(crate) fn plane(&self, channel: usize) -> Self {
+        Self {
+            channels: [channel; 3],
+            ..*self
+        }
+    }
+
+    /// Returns the decoder of pixels if given.
+    pub(crate) fn decoder(&self) -> Option<&'a Decoder> {
+        self.decoder
+    }
+
+    /// Returns channels of tables to decode subpixels with, see [`Decoder::decode()`].
+    pub(crate) fn channels(&self) -> [usize; 3] {
+        self.channels
+    }
+
+    /// Computes a sorting key of the pixel at the given position.
+    ///
+    /// The primary key is in upper bits and the secondary key for tie-breaking is in lower bits.
+    pub(crate) fn of(&self, pixel: &Rgb<u8>, x: u32, y: u32) -> u64 {
+        let decoder = self.decoder.map(|decoder| (decoder, self.channels));
+        let primary = key_of(self.key, decoder, pixel, x, y) as u64;
+        let secondary = match self.tie {
+            TieBreak::Key(key) => key_of(key, decoder, pixel, x, y),
+            _ => 0,
+        };
+        primary << 32 | secondary as u64
+    }
+
+    /// Sorts pixels of a line by keys with tie-breaking, using `scratch` as a working buffer.
+    pub(crate) fn sort<P: Copy>(&self, line: &mut [Entry<P>], scratch: &mut Vec<Entry<P>>) {
+        if let TieBreak::ReversePosition = self.tie {
+            line.reverse();
+        }
+
+        // NOTE: Stable sort keeps the original order (or reversed one) of ties
+        radix_sort(line, scratch);
+    }
+}
+
+/// Computes a sorting key of the pixel at the given position, decoded with the decoder and
+/// channels if given.
+///
+/// Keys are unsigned integers preserving the order of each key.
+fn key_of(
+    key: &Key,
+    decoder: Option<(&Decoder, [usize; 3])>,
+    pixel: &Rgb<u8>,
+    x: u32,
+    y: u32,
+) -> u32 {
+    let color = || match decoder {
+        Some((decoder, channels)) => PColor::from(decoder.decode8(pixel, channels)),
+        None => PColor::from(*pixel),
+    };
+    let linear = || match decoder {
+        Some((decoder, channels)) => decoder.decode(pixel, channels),
+        None => PColor::from(*pixel).linear_rgb(),
+    };
+
+    match key {
+        Key::Color => (color().as_raw() as u32) ^ 0x8000_0000,
+        Key::Brightness => color().brightness() as u32,
+        Key::Lightness => float_key(oklab_of_linear(linear()).0),
+        Key::CieLightness => float_key(lab_of_linear(linear()).0),
+        Key::Chroma => float_key(lch_of_lab(lab_of_linear(linear())).1),
+        Key::Hue => float_key(lch_of_lab(lab_of_linear(linear())).2),
+        Key::Image(image) => image.get_pixel(x, y).0[0] as u32,
+    }
+}
+
+/// Converts a non-negative float to an integer with the same order.
+fn float_key(value: f32) -> u32 {
+    // NOTE: Bits of non-negative floats are ordered as unsigned integers
+    value.max(0.0).to_bits()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Transfer;
+    use image::GrayImage;
+
+    #[test]
+    fn test_key_of_color() {
+        let c1 = Rgb([11, 220, 0]);
+        let c2 = Rgb([57, 162, 192]);
+        let c3 = Rgb([255, 255, 255]);
+        assert!(key_of(&Key::Color, None, &c1, 0, 0) < key_of(&Key::Color, None, &c2, 0, 0));
+        assert!(key_of(&Key::Color, None, &c2, 0, 0) < key_of(&Key::Color, None, &c3, 0, 0));
+        assert_eq!(
+            key_of(&Key::Color, None, &c1, 0, 0),
+            key_of(&Key::Color, None, &c1, 5, 5),
+            "independent from position"
+        );
+    }
+
+    #[test]
+    fn test_key_of_brightness() {
+        assert_eq!(
+            220,
+            key_of(&Key::Brightness, None, &Rgb([11, 220, 0]), 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_key_of_perceptual() {
+        let dark = Rgb([0, 0, 64]);
+        let light = Rgb([64, 64, 64]);
+        for key in [Key::Lightness, Key::CieLightness] {
+            assert!(key_of(&key, None, &dark, 0, 0) < key_of(&key, None, &light, 0, 0));
+        }
+
+        let gray = Rgb([128, 128, 128]);
+        let red = Rgb([255, 0, 0]);
+        assert!(key_of(&Key::Chroma, None, &gray, 0, 0) < key_of(&Key::Chroma, None, &red, 0, 0));
+
+        let blue = Rgb([0, 0, 255]);
+        assert!(key_of(&Key::Hue, None, &red, 0, 0) < key_of(&Key::Hue, None, &blue, 0, 0));
+    }
+
+    #[test]
+    fn test_key_of_decoded() {
+        let pixel = Rgb([0, 128, 64]);
+
+        let decoder = Decoder::new(&Transfer::Srgb).unwrap();
+        assert_eq!(
+            55,
+            key_of(&Key::Brightness, Some((&decoder, RGB)), &pixel, 0, 0)
+        );
+
+        let expected = key_of(&Key::Lightness, None, &pixel, 0, 0);
+        let actual = key_of(&Key::Lightness, Some((&decoder, RGB)), &pixel, 0, 0);
+        assert_eq!(expected, actual, "same as sRGB");
+
+        let decoder = Decoder::new(&Transfer::gamma(1.0)).unwrap();
+        let expected = key_of(&Key::Color, None, &pixel, 0, 0);
+        let actual = key_of(&Key::Color, Some((&decoder, RGB)), &pixel, 0, 0);
+        assert_eq!(expected, actual, "identity");
+    }
+
+    #[test]
+    fn test_key_of_image() {
+        let key = Key::Image(GrayImage::from_raw(2, 2, vec![0, 10, 20, 30]).unwrap());
+        let pixel = Rgb([255, 255, 255]);
+        assert_eq!(0, key_of(&key, None, &pixel, 0, 0));
+        assert_eq!(10, key_of(&key, None, &pixel, 1, 0));
+        assert_eq!(20, key_of(&key, None, &pixel, 0, 1));
+        assert_eq!(30, key_of(&key, None, &pixel, 1, 1));
+    }
+
+    #[test]
+    fn test_float_key() {
+        assert!(float_key(0.0) < float_key(1e-9));
+        assert!(float_key(0.5) < float_key(1.0));
+        assert!(float_key(99.0) < float_key(100.0));
+        assert_eq!(float_key(0.0), float_key(-1e-6), "clamp negative");
+    }
+
+    /// Sorts pixels of a line and returns them.
+    fn sort_line(keys: &Keys, pixels: &[Rgb<u8>]) -> Vec<Rgb<u8>> {
+        let mut line: Vec<_> = pixels
+            .iter()
+            .enumerate()
+            .map(|(x, pixel)| (keys.of(pixel, x as u32, 0), *pixel))
+            .collect();
+        keys.sort(&mut line, &mut Vec::new());
+        line.into_iter().map(|(_, pixel)| pixel).collect()
+    }
+
+    #[test]
+    fn test_keys_sort_position() {
+        let pixels = [
+            Rgb([0, 0, 200]),
+            Rgb([0, 100, 0]),
+            Rgb([200, 0, 0]),
+            Rgb([0, 0, 100]),
+        ];
+        let keys = Keys::new(&Key::Brightness, &TieBreak::Position, None);
+        let expected = vec![pixels[1], pixels[3], pixels[0], pixels[2]];
+        assert_eq!(expected, sort_line(&keys, &pixels));
+    }
+
+    #[test]
+    fn test_keys_sort_reverse_position() {
+        let pixels = [
+            Rgb([0, 0, 200]),
+            Rgb([0, 100, 0]),
+            Rgb([200, 0, 0]),
+            Rgb([0, 0, 100]),
+        ];
+        let keys = Keys::new(&Key::Brightness, &TieBreak::ReversePosition, None);
+        let expected = vec![pixels[3], pixels[1], pixels[2], pixels[0]];
+        assert_eq!(expected, sort_line(&keys, &pixels));
+    }
+
+    #[test]
+    fn test_keys_sort_secondary_key() {
+        let pixels = [
+            Rgb([0, 0, 200]),
+            Rgb([200, 0, 0]),
+            Rgb([0, 100, 0]),
+            Rgb([0, 200, 0]),
+        ];
+        let tie = TieBreak::Key(Key::Hue);
+        let keys = Keys::new(&Key::Brightness, &tie, None);
+        let expected = vec![pixels[2], pixels[1], pixels[3], pixels[0]];
+        assert_eq!(expected, sort_line(&keys, &pixels));
+    }
+}
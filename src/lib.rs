@@ -1,9 +1,31 @@
+mod blend;
+#[cfg(feature = "capi")]
+pub mod capi;
 mod color;
+mod edge;
 mod finder;
+mod key;
 mod line_sorter;
 mod options;
+mod path;
+mod progress;
+#[cfg(feature = "python")]
+mod python;
+mod radix;
 mod sort;
+mod stream;
+mod threshold;
+mod transfer;
+mod transpose;
+#[cfg(feature = "wasm")]
+mod wasm;
 
 pub use color::PColor;
-pub use options::{Direction, Mode, Options, DEFAULT_BLACK, DEFAULT_BRIGHTNESS, DEFAULT_WHITE};
-pub use sort::{sort, sort_with_options};
+pub use options::{
+    Alpha, Blend, BlendMode, Channels, Direction, Guide, Key, Mode, Options, Path, TieBreak,
+    Variation, DEFAULT_BLACK, DEFAULT_BRIGHTNESS, DEFAULT_EDGE, DEFAULT_WHITE,
+};
+pub use progress::{Cancellation, Cancelled};
+pub use sort::{sort, sort_raw_pixels, sort_rgba, sort_with_options, sort_with_progress};
+pub use stream::{sort_rows, sort_rows_from, sort_tiled, SortedRows};
+pub use transfer::Transfer;
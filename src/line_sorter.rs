@@ -1,67 +1,346 @@
-use crate::{color::PColor, finder, Options};
+use image::{Pixel, Rgb, Rgba};
 
-pub(crate) fn sort_column(buf: &mut image::RgbImage, x: u32, options: &Options) {
-    let height = buf.height();
+use crate::{
+    blend::mix_weighted, finder, key::Keys, radix::Entry, transfer::Decoder, Alpha, BlendMode,
+    Channels, Mode, Options,
+};
 
-    let mut y = 0;
-    let mut y_end = 0;
+/// Sorter of intervals in rows, reusing buffers across intervals and rows.
+///
+/// Columns are sorted as rows of the transposed image.
+pub(crate) struct LineSorter<'a> {
+    options: &'a Options,
+    line: Vec<Entry>,
+    scratch: Vec<Entry>,
+    unsorted: Vec<Rgba<u8>>,
+    planes: Planes,
+}
+
+/// Buffers of a channel plane of a row, seen as RGB subpixels of a grayscale row.
+#[derive(Default)]
+struct Planes {
+    row: Vec<u8>,
+    guide: Vec<u8>,
+    alpha: Vec<u8>,
+}
+
+impl<'a> LineSorter<'a> {
+    pub(crate) fn new(options: &'a Options) -> Self {
+        Self {
+            options,
+            line: Vec::new(),
+            scratch: Vec::new(),
+            unsorted: Vec::new(),
+            planes: Planes::default(),
+        }
+    }
 
-    while y_end < height - 1 {
-        y = match finder::get_first_y(buf, x, y, &options.mode) {
-            Some(y) => y,
-            _ => break,
+    /// Sorts `row`, RGB subpixels of the row `y`, by `keys` detecting intervals on `guide` if
+    /// given.
+    /// Alpha values in `alpha` are moved along with pixels, and treated by [`Options::alpha`],
+    /// if given.
+    ///
+    /// With [`Channels::Separate`], each channel plane is sorted separately as a grayscale row,
+    /// detecting intervals on the same plane of `guide`, and alpha values stay in place.
+    pub(crate) fn sort_row(
+        &mut self,
+        keys: &Keys,
+        row: &mut [u8],
+        alpha: Option<&mut [u8]>,
+        guide: Option<&[u8]>,
+        y: u32,
+    ) {
+        let options = self.options;
+        let modes = match &options.channels {
+            Channels::Joint => {
+                return self.sort_intervals(keys, &options.mode, row, alpha, guide, y)
+            }
+            Channels::Separate(modes) => modes,
         };
 
-        y_end = finder::get_next_y(buf, x, y, &options.mode);
+        let mut planes = std::mem::take(&mut self.planes);
+        let Planes {
+            row: row_plane,
+            guide: guide_plane,
+            alpha: alpha_plane,
+        } = &mut planes;
+        for (channel, mode) in modes.iter().enumerate() {
+            plane_of(row, channel, row_plane);
+            let guide = guide.map(|guide| {
+                plane_of(guide, channel, guide_plane);
+                &guide_plane[..]
+            });
+            let alpha = alpha.as_deref().map(|alpha| {
+                alpha_plane.clear();
+                alpha_plane.extend_from_slice(alpha);
+                &mut alpha_plane[..]
+            });
 
-        let len = y_end - y;
-        let mut line = Vec::with_capacity(len as usize);
+            // NOTE: Subpixels of the plane are keyed and feathered with the curve of the channel
+            self.sort_intervals(&keys.plane(channel), mode, row_plane, alpha, guide, y);
 
-        for y in y..y_end {
-            let pixel = buf.get_pixel(x, y);
-            line.push(*pixel);
+            for (pixel, plane) in row.chunks_exact_mut(3).zip(row_plane.chunks_exact(3)) {
+                pixel[channel] = plane[0];
+            }
         }
+        self.planes = planes;
+    }
+
+    /// Sorts intervals of `row` detected with `mode` by `keys` as [`LineSorter::sort_row()`] does.
+    fn sort_intervals(
+        &mut self,
+        keys: &Keys,
+        mode: &Mode,
+        row: &mut [u8],
+        mut alpha: Option<&mut [u8]>,
+        guide: Option<&[u8]>,
+        y: u32,
+    ) {
+        let width = (row.len() / 3) as u32;
+        let variation = &self.options.variation;
+
+        let alpha_mode = match alpha {
+            Some(_) => self.options.alpha,
+            None => Alpha::Ignore,
+        };
+
+        let mut x = 0;
+        let mut x_end = 0;
 
-        line.sort_by(|a, b| PColor::from(*a).cmp(&(*b).into()));
+        while x_end < width - 1 {
+            x = match finder::get_first_x(guide.unwrap_or(row), x, y, mode, variation) {
+                Some(x) => x,
+                _ => break,
+            };
 
-        for i in 0..len {
-            let pixel = line.get(i as usize).unwrap();
-            buf.put_pixel(x, y + i, *pixel);
+            x_end = finder::get_next_x(guide.unwrap_or(row), x, y, mode, variation);
+
+            if alpha_mode == Alpha::Break {
+                let interval = &alpha.as_ref().unwrap()[x as usize..x_end as usize];
+                if let Some(i) = interval.iter().position(|&a| a == 0) {
+                    x_end = x + i as u32;
+                }
+            }
+
+            self.line.clear();
+            for (x, pixel) in (x..x_end).zip(row[x as usize * 3..x_end as usize * 3].chunks(3)) {
+                let pixel = Rgb::from_slice(pixel);
+                let [r, g, b] = pixel.0;
+                let a = alpha.as_ref().map_or(255, |alpha| alpha[x as usize]);
+                if alpha_mode == Alpha::Exclude && a == 0 {
+                    continue;
+                }
+                let key = match alpha_mode {
+                    Alpha::Premultiply => keys.of(&premultiply(pixel, a), x, y),
+                    _ => keys.of(pixel, x, y),
+                };
+                self.line.push((key, Rgba([r, g, b, a])));
+            }
+
+            let feather = self.options.feather;
+            if feather > 0 {
+                self.unsorted.clear();
+                self.unsorted
+                    .extend(self.line.iter().map(|(_, pixel)| *pixel));
+            }
+
+            keys.sort(&mut self.line, &mut self.scratch);
+
+            if feather > 0 {
+                let fades_alpha = matches!(alpha_mode, Alpha::Ignore | Alpha::Premultiply);
+                let decoder = keys.decoder().map(|decoder| (decoder, keys.channels()));
+                feather_ends(
+                    &mut self.line,
+                    &self.unsorted,
+                    feather,
+                    decoder,
+                    fades_alpha,
+                );
+            }
+
+            let interval = row[x as usize * 3..x_end as usize * 3].chunks_mut(3);
+            match alpha.as_mut() {
+                Some(alpha) if alpha_mode == Alpha::Exclude => {
+                    let opaque = interval
+                        .zip(alpha[x as usize..x_end as usize].iter_mut())
+                        .filter(|(_, a)| **a != 0);
+                    for ((subpixels, a), (_, pixel)) in opaque.zip(self.line.iter()) {
+                        subpixels.copy_from_slice(&pixel.0[..3]);
+                        *a = pixel.0[3];
+                    }
+                }
+                alpha => {
+                    for (subpixels, (_, pixel)) in interval.zip(self.line.iter()) {
+                        subpixels.copy_from_slice(&pixel.0[..3]);
+                    }
+                    if let Some(alpha) = alpha {
+                        let interval = &mut alpha[x as usize..x_end as usize];
+                        for (a, (_, pixel)) in interval.iter_mut().zip(self.line.iter()) {
+                            *a = pixel.0[3];
+                        }
+                    }
+                }
+            }
+
+            x = x_end + 1;
         }
+    }
+}
+
+/// Copies a channel of RGB subpixels in `row` to all subpixels of `plane`.
+fn plane_of(row: &[u8], channel: usize, plane: &mut Vec<u8>) {
+    plane.clear();
+    plane.extend(row.chunks_exact(3).flat_map(|pixel| [pixel[channel]; 3]));
+}
 
-        y = y_end + 1;
+/// Cross-fades the first and last `feather` pixels of a sorted line with `unsorted` pixels at
+/// the same positions, ramping linearly from the unsorted pixels at the ends of the line.
+///
+/// Colors are mixed in linear light if `decoder` is given, decoding subpixels with tables of its
+/// channels, and alpha values are cross-faded only if `fades_alpha`, otherwise kept as sorted.
+fn feather_ends(
+    line: &mut [Entry],
+    unsorted: &[Rgba<u8>],
+    feather: u32,
+    decoder: Option<(&Decoder, [usize; 3])>,
+    fades_alpha: bool,
+) {
+    let len = line.len();
+    let ramp = feather as usize + 1;
+    let head = ramp.min(len);
+    let ends = (0..head).chain(len.saturating_sub(ramp).max(head)..len);
+    for i in ends {
+        let weight = (i + 1).min(len - i);
+        if weight >= ramp {
+            continue;
+        }
+        let (_, pixel) = &mut line[i];
+        let weight = (weight as u32, ramp as u32);
+        let (color, alpha) = pixel.0.split_at_mut(3);
+        for (subpixel, (value, &original)) in color.iter_mut().zip(unsorted[i].0.iter()).enumerate()
+        {
+            let (decoder, channel) = match decoder {
+                Some((decoder, channels)) => (Some(decoder), channels[subpixel]),
+                None => (None, subpixel),
+            };
+            *value = mix_weighted(
+                decoder,
+                BlendMode::Normal,
+                channel,
+                original,
+                *value,
+                weight,
+            );
+        }
+        if fades_alpha {
+            let original = unsorted[i].0[3];
+            alpha[0] = mix_weighted(None, BlendMode::Normal, 3, original, alpha[0], weight);
+        }
     }
 }
 
-pub(crate) fn sort_row(buf: &mut image::RgbImage, y: u32, options: &Options) {
-    let width = buf.width();
+/// Premultiplies RGB values of a pixel by alpha.
+fn premultiply(pixel: &Rgb<u8>, alpha: u8) -> Rgb<u8> {
+    pixel.map(|c| ((c as u32 * alpha as u32 + 127) / 255) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut x = 0;
-    let mut x_end = 0;
+    use crate::{transfer::RGB, Key, Transfer};
 
-    while x_end < width - 1 {
-        x = match finder::get_first_x(buf, x, y, &options.mode) {
-            Some(x) => x,
-            _ => break,
+    /// Sorts a row of pixels with the options.
+    fn sort_row(pixels: &[[u8; 3]], options: &Options) -> Vec<[u8; 3]> {
+        let decoder = Decoder::new(&options.transfer);
+        let keys = Keys::new(&options.key, &options.tie, decoder.as_ref());
+        let mut row = pixels.concat();
+        LineSorter::new(options).sort_row(&keys, &mut row, None, None, 0);
+        row.chunks_exact(3).map(|p| [p[0], p[1], p[2]]).collect()
+    }
+
+    /// Creates a transfer of gamma curves of channels.
+    fn gammas(gammas: [f32; 3]) -> Transfer {
+        let curve = |gamma| match Transfer::gamma(gamma) {
+            Transfer::Tables(tables) => tables[0],
+            _ => unreachable!(),
         };
+        Transfer::Tables(Box::new(gammas.map(curve)))
+    }
 
-        x_end = finder::get_next_x(buf, x, y, &options.mode);
+    #[test]
+    fn test_sort_row_separate_curves() {
+        // NOTE: The red curve decodes both values to zero, so they tie only in the red plane
+        let options = Options {
+            key: Key::Brightness,
+            transfer: gammas([8.0, 1.0, 1.0]),
+            channels: Channels::separate(Mode::Brightness(0)),
+            ..Default::default()
+        };
+        let sorted = sort_row(&[[20; 3], [10; 3], [255; 3]], &options);
+        assert_eq!(vec![[20, 10, 10], [10, 20, 20], [255; 3]], sorted);
+    }
 
-        let len = x_end - x;
-        let mut line = Vec::with_capacity(len as usize);
+    #[test]
+    fn test_sort_row_separate_feather() {
+        // NOTE: Ends of each plane are half mixed in linear light of the curve of the channel
+        let options = Options {
+            key: Key::Brightness,
+            transfer: gammas([1.0, 2.2, 2.2]),
+            channels: Channels::separate(Mode::Brightness(0)),
+            feather: 1,
+            ..Default::default()
+        };
+        let sorted = sort_row(&[[255; 3], [100; 3], [50; 3], [0; 3]], &options);
+        let expected = vec![[177, 197, 197], [177, 197, 197], [50; 3], [0; 3]];
+        assert_eq!(expected, sorted);
+    }
 
-        for x in x..x_end {
-            let pixel = buf.get_pixel(x, y);
-            line.push(*pixel);
-        }
+    fn line(values: &[u8]) -> Vec<Entry> {
+        values.iter().map(|&v| (0, Rgba([v, v, v, v]))).collect()
+    }
 
-        line.sort_by(|a, b| PColor::from(*a).cmp(&(*b).into()));
+    fn values(line: &[Entry]) -> Vec<u8> {
+        line.iter().map(|(_, pixel)| pixel.0[0]).collect()
+    }
 
-        for i in 0..len {
-            let pixel = line.get(i as usize).unwrap();
-            buf.put_pixel(x + i, y, *pixel);
-        }
+    #[test]
+    fn test_feather_ends() {
+        let unsorted: Vec<_> = line(&[0; 8]).iter().map(|(_, p)| *p).collect();
+        let mut sorted = line(&[90; 8]);
+        feather_ends(&mut sorted, &unsorted, 2, None, true);
+        assert_eq!(vec![30, 60, 90, 90, 90, 90, 60, 30], values(&sorted));
+    }
+
+    #[test]
+    fn test_feather_ends_linear() {
+        let unsorted: Vec<_> = line(&[0; 3]).iter().map(|(_, p)| *p).collect();
+        let mut sorted = line(&[255; 3]);
+        let decoder = Decoder::new(&Transfer::Srgb).unwrap();
+        feather_ends(&mut sorted, &unsorted, 1, Some((&decoder, RGB)), true);
+        assert_eq!(vec![188, 255, 188], values(&sorted));
+        let alpha: Vec<_> = sorted.iter().map(|(_, pixel)| pixel.0[3]).collect();
+        assert_eq!(vec![128, 255, 128], alpha, "alpha is not decoded");
+    }
+
+    #[test]
+    fn test_feather_ends_keeping_alpha() {
+        let unsorted: Vec<_> = line(&[0; 8]).iter().map(|(_, p)| *p).collect();
+        let mut sorted = line(&[90; 8]);
+        feather_ends(&mut sorted, &unsorted, 2, None, false);
+        assert_eq!(vec![30, 60, 90, 90, 90, 90, 60, 30], values(&sorted));
+        assert!(sorted.iter().all(|(_, pixel)| pixel.0[3] == 90), "alpha");
+    }
 
-        x = x_end + 1;
+    #[test]
+    fn test_feather_ends_short() {
+        let unsorted: Vec<_> = line(&[0; 3]).iter().map(|(_, p)| *p).collect();
+        let mut sorted = line(&[90; 3]);
+        feather_ends(&mut sorted, &unsorted, 2, None, true);
+        assert_eq!(
+            vec![30, 60, 30],
+            values(&sorted),
+            "ramps meet in the middle"
+        );
     }
 }
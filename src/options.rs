@@ -1,8 +1,12 @@
+use image::{GrayImage, RgbImage};
 use once_cell::sync::Lazy;
 
-use crate::PColor;
+use crate::{PColor, Transfer};
 
 /// Options to configure behaviours.
+///
+/// Sorting is stable: pixels with equal keys are ordered by [`Options::tie`],
+/// so the output is reproducible for the same input and options.
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct Options {
     /// Sorting mode.
@@ -10,6 +14,37 @@ pub struct Options {
 
     /// Sorting direction.
     pub direction: Direction,
+
+    /// Spatial variation of the threshold.
+    pub variation: Variation,
+
+    /// Sorting key of pixels.
+    pub key: Key,
+
+    /// Tie-breaking of pixels with equal keys.
+    pub tie: TieBreak,
+
+    /// Image to detect intervals on.
+    pub guide: Guide,
+
+    /// Transfer function to compute keys and thresholds in linear light.
+    pub transfer: Transfer,
+
+    /// Treatment of transparent pixels in RGBA images.
+    pub alpha: Alpha,
+
+    /// Sorting of color channels, whole pixels or each channel separately.
+    pub channels: Channels,
+
+    /// Compositing of the sorted image over the original.
+    pub blend: Blend,
+
+    /// Number of pixels at each end of intervals cross-faded with the original pixels,
+    /// so streaks blend into unsorted regions. `0` keeps hard ends.
+    ///
+    /// Colors are cross-faded in linear light decoded by [`Options::transfer`]. Alpha values are
+    /// cross-faded too, except with [`Alpha::Break`] and [`Alpha::Exclude`] keeping them as sorted.
+    pub feather: u32,
 }
 
 /// Default value of [`Mode::Black`].
@@ -21,6 +56,9 @@ pub static DEFAULT_BRIGHTNESS: u8 = 60;
 /// Default value of [`Mode::White`].
 pub static DEFAULT_WHITE: Lazy<PColor> = Lazy::new(|| PColor::new(57, 162, 192));
 
+/// Default value of [`Mode::Edge`].
+pub static DEFAULT_EDGE: u8 = 64;
+
 /// Sorting modes.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Mode {
@@ -32,6 +70,12 @@ pub enum Mode {
 
     /// White mode with a threshold color.
     White(PColor),
+
+    /// Edge mode with a threshold of edge strength `0` to `255`.
+    ///
+    /// Intervals end at pixels where the gradient magnitude computed by Sobel operator
+    /// reaches the threshold, so objects keep their silhouettes.
+    Edge(u8),
 }
 
 impl Default for Mode {
@@ -55,12 +99,18 @@ impl Mode {
     pub fn white() -> Self {
         Self::White(DEFAULT_WHITE.clone())
     }
+
+    /// Edge mode with a default threshold.
+    pub fn edge() -> Self {
+        Self::Edge(DEFAULT_EDGE)
+    }
 }
 
 /// Sorting direction.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub enum Direction {
     /// Both column and row.
+    #[default]
     Both,
 
     /// Only column.
@@ -68,28 +118,268 @@ pub enum Direction {
 
     /// Only row.
     Row,
+
+    /// Along sequences of pixels of a path, instead of columns and rows.
+    ///
+    /// Threshold gradients, threshold maps and key images are looked up at the positions of
+    /// pixels in the image, and jitter varies by sequence.
+    Path(Path),
 }
 
-impl Default for Direction {
-    fn default() -> Self {
-        Direction::Both
-    }
+/// Paths to sort pixels along, visiting each pixel of the image exactly once.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Path {
+    /// Concentric circles around `center` from inside out, each walked clockwise from the left.
+    Circles { center: (u32, u32) },
+
+    /// Rays from `center` outward to each pixel of the image border, clockwise from the left.
+    ///
+    /// Each pixel belongs to the ray through it, so rays near the center are sparse and start
+    /// further out. `center` is clamped into the image.
+    Rays { center: (u32, u32) },
+
+    /// An Archimedean spiral winding clockwise out from `center`, with `spacing` pixels between
+    /// turns, as a single sequence.
+    Spiral { center: (u32, u32), spacing: u32 },
+
+    /// Hilbert curve over the smallest square of a power of two covering the image,
+    /// as a single sequence skipping outside pixels.
+    Hilbert,
+
+    /// Z-order (Morton order) curve, as a single sequence.
+    ZOrder,
+
+    /// Rows walked alternately left to right and right to left, as a single sequence.
+    Serpentine,
+
+    /// Streamlines of a flow field of the same size with the sorted image, whose red and green
+    /// values are horizontal and vertical components of vectors with `128` as zero.
+    ///
+    /// Streamlines are traced forward from unvisited pixels in row-major order, and end at
+    /// zero vectors, borders or visited pixels.
+    Flow(RgbImage),
 }
 
 impl Direction {
+    /// Rays from the center, see [`Path::Rays`].
+    pub fn rays(center: (u32, u32)) -> Self {
+        Self::Path(Path::Rays { center })
+    }
+
+    /// Concentric circles around the center, see [`Path::Circles`].
+    pub fn circles(center: (u32, u32)) -> Self {
+        Self::Path(Path::Circles { center })
+    }
+
     /// Checks if this direction has [`Direction::Column`].
     pub fn has_column(&self) -> bool {
-        match self {
-            Self::Both | Self::Column => true,
-            _ => false,
-        }
+        matches!(self, Self::Both | Self::Column)
     }
 
     /// Checks if this direction has [`Direction::Row`].
     pub fn has_row(&self) -> bool {
-        match self {
-            Self::Both | Self::Row => true,
-            _ => false,
+        matches!(self, Self::Both | Self::Row)
+    }
+}
+
+/// Spatial variation of the threshold.
+///
+/// Gradients blend the threshold of [`Options::mode`] toward the threshold of `end` by position.
+/// `end` must be the same kind of mode, otherwise the threshold is not varied.
+/// With [`Channels::Separate`], the mode of each plane is varied only toward `end` of its kind.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum Variation {
+    /// Same threshold over the whole image.
+    #[default]
+    Uniform,
+
+    /// Linear gradient along the segment from `from` to `to`.
+    Linear {
+        from: (u32, u32),
+        to: (u32, u32),
+        end: Mode,
+    },
+
+    /// Radial gradient from `center` to `radius` pixels away.
+    Radial {
+        center: (u32, u32),
+        radius: u32,
+        end: Mode,
+    },
+
+    /// Random offset within `-amount` to `amount` per row and per column.
+    Jitter { amount: u8, seed: u64 },
+
+    /// Threshold map painted as a grayscale image of the same size with the sorted image.
+    ///
+    /// Without `end`, each value of the map is used as the threshold of the kind of
+    /// [`Options::mode`] as is, a gray color for [`Mode::Black`] and [`Mode::White`].
+    ///
+    /// With `end`, a value `t` of the map blends the threshold of [`Options::mode`] toward the
+    /// threshold of `end`, as `mode + (end - mode) * t / 255` rounded, so `0` keeps `mode` and
+    /// `255` reaches `end`. For example, with `Mode::Brightness(100)` and
+    /// `end: Some(Mode::Brightness(200))`, a value `128` of the map means the threshold `150`.
+    Map { map: GrayImage, end: Option<Mode> },
+}
+
+/// Sorting keys of pixels.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum Key {
+    /// Color of the pixel itself, ordered as [`PColor`].
+    #[default]
+    Color,
+
+    /// Brightness of Processing, see [`PColor::brightness()`].
+    Brightness,
+
+    /// Lightness of OKLab, see [`PColor::oklab()`].
+    Lightness,
+
+    /// Lightness `L*` of CIELAB, see [`PColor::lab()`].
+    CieLightness,
+
+    /// Chroma of CIE LCh, see [`PColor::lch()`].
+    Chroma,
+
+    /// Hue of CIE LCh, see [`PColor::lch()`].
+    Hue,
+
+    /// Value of a grayscale image of the same size with the sorted image,
+    /// taken at the original position of the pixel (e.g. a depth map or a noise texture).
+    Image(GrayImage),
+}
+
+/// Tie-breaking of pixels with equal keys.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum TieBreak {
+    /// Keeps the original order.
+    #[default]
+    Position,
+
+    /// Reverses the original order.
+    ReversePosition,
+
+    /// Compares with a secondary key, then keeps the original order.
+    Key(Key),
+}
+
+/// Images to detect intervals on.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum Guide {
+    /// The sorted image itself, so the row pass sees pixels already sorted by the column pass.
+    #[default]
+    Target,
+
+    /// The untouched input, so both passes detect intervals consistently.
+    Original,
+
+    /// An external image of the same size with the sorted image (e.g. an edge map or a segmentation).
+    Image(RgbImage),
+}
+
+/// Treatments of transparent pixels, whose alpha values are `0`, in RGBA images.
+///
+/// Alpha values always move along with their pixels.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Alpha {
+    /// Sorts pixels by their RGB values regardless of alpha values.
+    #[default]
+    Ignore,
+
+    /// Ends intervals at transparent pixels, which stay in place.
+    Break,
+
+    /// Keeps transparent pixels in place and sorts the other pixels of intervals around them.
+    Exclude,
+
+    /// Computes keys of colors premultiplied by alpha, so transparent pixels sort as black.
+    Premultiply,
+}
+
+/// Sorting of color channels.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum Channels {
+    /// Sorts whole pixels with [`Options::mode`].
+    #[default]
+    Joint,
+
+    /// Sorts each of red, green and blue planes separately as a grayscale image, detecting
+    /// intervals with the modes of the planes in order, so channels streak apart.
+    ///
+    /// Alpha values stay in place.
+    Separate([Mode; 3]),
+}
+
+impl Channels {
+    /// Separate channels with the same mode for all planes.
+    pub fn separate(mode: Mode) -> Self {
+        Self::Separate([mode.clone(), mode.clone(), mode])
+    }
+}
+
+/// Compositing of the sorted image over the original.
+///
+/// RGB values are blended in linear light decoded by [`Options::transfer`], and alpha values stay
+/// as sorted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Blend {
+    /// Blend mode of the sorted image.
+    pub mode: BlendMode,
+
+    /// Opacity of the sorted image from `0` (the original) to `255`.
+    pub opacity: u8,
+
+    /// Mask of the same size with the image, weighting the opacity by its value.
+    pub mask: Option<GrayImage>,
+}
+
+impl Default for Blend {
+    fn default() -> Self {
+        Self {
+            mode: BlendMode::Normal,
+            opacity: 255,
+            mask: None,
+        }
+    }
+}
+
+impl Blend {
+    /// Returns `true` if the sorted image replaces the original as it is.
+    pub(crate) fn is_noop(&self) -> bool {
+        self.mode == BlendMode::Normal && self.opacity == 255 && self.mask.is_none()
+    }
+}
+
+/// Blend modes of the sorted image over the original.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum BlendMode {
+    /// The sorted image.
+    #[default]
+    Normal,
+
+    /// Inverted product of inverted images, which lightens.
+    Screen,
+
+    /// Product of images, which darkens.
+    Multiply,
+
+    /// Maximum of images by channel.
+    Lighten,
+
+    /// Minimum of images by channel.
+    Darken,
+
+    /// Absolute difference of images by channel.
+    Difference,
+}
+
+impl Options {
+    /// Returns `true` if intervals of any plane are detected on edge maps.
+    pub(crate) fn detects_edges(&self) -> bool {
+        let is_edge = |mode: &Mode| matches!(mode, Mode::Edge(_));
+        match &self.channels {
+            Channels::Joint => is_edge(&self.mode),
+            Channels::Separate(modes) => modes.iter().any(is_edge),
         }
     }
 }
@@ -103,6 +393,19 @@ mod tests {
         let expected = Options {
             mode: Mode::Brightness(60),
             direction: Direction::Both,
+            variation: Variation::Uniform,
+            key: Key::Color,
+            tie: TieBreak::Position,
+            guide: Guide::Target,
+            transfer: Transfer::Encoded,
+            alpha: Alpha::Ignore,
+            channels: Channels::Joint,
+            blend: Blend {
+                mode: BlendMode::Normal,
+                opacity: 255,
+                mask: None,
+            },
+            feather: 0,
         };
         assert_eq!(expected, Options::default());
     }
@@ -134,6 +437,69 @@ mod tests {
         assert_eq!(expected, Mode::white());
     }
 
+    #[test]
+    fn test_mode_edge() {
+        let value = 64;
+        let expected = Mode::Edge(value);
+        assert_eq!(expected, Mode::edge());
+    }
+
+    #[test]
+    fn test_channels_separate() {
+        let expected = Channels::Separate([Mode::white(), Mode::white(), Mode::white()]);
+        assert_eq!(expected, Channels::separate(Mode::white()));
+    }
+
+    #[test]
+    fn test_direction_paths() {
+        let expected = Direction::Path(Path::Rays { center: (1, 2) });
+        assert_eq!(expected, Direction::rays((1, 2)));
+
+        let expected = Direction::Path(Path::Circles { center: (1, 2) });
+        assert_eq!(expected, Direction::circles((1, 2)));
+
+        assert!(!expected.has_column() && !expected.has_row());
+    }
+
+    #[test]
+    fn test_blend_is_noop() {
+        assert!(Blend::default().is_noop());
+
+        let blend = Blend {
+            opacity: 128,
+            ..Default::default()
+        };
+        assert!(!blend.is_noop(), "translucent");
+
+        let blend = Blend {
+            mask: Some(GrayImage::new(1, 1)),
+            ..Default::default()
+        };
+        assert!(!blend.is_noop(), "masked");
+    }
+
+    #[test]
+    fn test_options_detects_edges() {
+        let options = Options {
+            mode: Mode::edge(),
+            ..Default::default()
+        };
+        assert!(options.detects_edges());
+
+        let options = Options {
+            mode: Mode::edge(),
+            channels: Channels::separate(Mode::brightness()),
+            ..Default::default()
+        };
+        assert!(!options.detects_edges(), "mode is unused");
+
+        let options = Options {
+            channels: Channels::Separate([Mode::brightness(), Mode::edge(), Mode::white()]),
+            ..Default::default()
+        };
+        assert!(options.detects_edges());
+    }
+
     #[test]
     fn test_direction_default() {
         let expected = Direction::Both;
@@ -153,4 +519,28 @@ mod tests {
         assert!(!Direction::Column.has_row());
         assert!(Direction::Row.has_row());
     }
+
+    #[test]
+    fn test_variation_default() {
+        let expected = Variation::Uniform;
+        assert_eq!(expected, Variation::default());
+    }
+
+    #[test]
+    fn test_key_default() {
+        let expected = Key::Color;
+        assert_eq!(expected, Key::default());
+    }
+
+    #[test]
+    fn test_tie_break_default() {
+        let expected = TieBreak::Position;
+        assert_eq!(expected, TieBreak::default());
+    }
+
+    #[test]
+    fn test_guide_default() {
+        let expected = Guide::Target;
+        assert_eq!(expected, Guide::default());
+    }
 }
@@ -0,0 +1,512 @@
+use std::f32::consts::PI;
+
+use image::{ImageBuffer, Luma, Pixel, RgbImage};
+
+use crate::threshold::{linear_factor, radial_factor};
+use crate::{Blend, Direction, Guide, Key, Options, Path, TieBreak, Variation};
+
+/// Sequence of pixel positions along a path.
+pub(crate) type Sequence = Vec<(u32, u32)>;
+
+/// Splits an image of the size into non-empty sequences along the path, covering each pixel
+/// exactly once.
+pub(crate) fn sequences_of(path: &Path, width: u32, height: u32) -> Vec<Sequence> {
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+
+    match path {
+        Path::Circles { center } => circles(*center, width, height),
+        Path::Rays { center } => rays(*center, width, height),
+        Path::Spiral { center, spacing } => vec![spiral(*center, *spacing, width, height)],
+        Path::Hilbert => {
+            let side = width.max(height).next_power_of_two();
+            vec![curve(width, height, |x, y| hilbert_index(side, x, y))]
+        }
+        Path::ZOrder => vec![curve(width, height, z_index)],
+        Path::Serpentine => vec![curve(width, height, |x, y| {
+            let x = if y % 2 == 0 { x } else { width - 1 - x };
+            y as u64 * width as u64 + x as u64
+        })],
+        Path::Flow(field) => flow(field),
+    }
+}
+
+/// Copies options to sort sequences as rows, before spatial options are gathered along each
+/// sequence by [`gather_options()`].
+///
+/// The guide and the blend are not parts of the copied options as
+/// [`crate::transpose::transpose_options()`] does.
+pub(crate) fn sequence_options(options: &Options) -> Options {
+    Options {
+        mode: options.mode.clone(),
+        direction: Direction::Row,
+        variation: options.variation.clone(),
+        key: options.key.clone(),
+        tie: options.tie.clone(),
+        guide: Guide::Target,
+        transfer: options.transfer.clone(),
+        alpha: options.alpha,
+        channels: options.channels.clone(),
+        blend: Blend::default(),
+        feather: options.feather,
+    }
+}
+
+/// Gathers spatial options of `original` along the sequence at `index` into `options`, to sort
+/// the sequence as the row `0`, its pixel at `x` being looked up at `(x, 0)`.
+///
+/// Gradients are rendered into threshold maps, and jitter is seeded by the index.
+pub(crate) fn gather_options(
+    options: &mut Options,
+    original: &Options,
+    sequence: &Sequence,
+    index: usize,
+) {
+    if let Some(variation) = sequence_variation(&original.variation, sequence, index) {
+        options.variation = variation;
+    }
+    if let Some(key) = sequence_key(&original.key, sequence) {
+        options.key = key;
+    }
+    if let TieBreak::Key(key) = &original.tie {
+        if let Some(key) = sequence_key(key, sequence) {
+            options.tie = TieBreak::Key(key);
+        }
+    }
+}
+
+fn sequence_variation(
+    variation: &Variation,
+    sequence: &Sequence,
+    index: usize,
+) -> Option<Variation> {
+    let variation = match variation {
+        Variation::Linear { from, to, end } => Variation::Map {
+            map: gather(sequence, |x, y| Luma([linear_factor(*from, *to, x, y)])),
+            end: Some(end.clone()),
+        },
+        Variation::Radial {
+            center,
+            radius,
+            end,
+        } => Variation::Map {
+            map: gather(sequence, |x, y| {
+                Luma([radial_factor(*center, *radius, x, y)])
+            }),
+            end: Some(end.clone()),
+        },
+        Variation::Map { map, end } => Variation::Map {
+            map: gather(sequence, |x, y| *map.get_pixel(x, y)),
+            end: end.clone(),
+        },
+        // NOTE: Jitter offsets of lines are hashed from `seed ^ y`
+        Variation::Jitter { amount, seed } => Variation::Jitter {
+            amount: *amount,
+            seed: seed ^ index as u64,
+        },
+        _ => return None,
+    };
+    Some(variation)
+}
+
+fn sequence_key(key: &Key, sequence: &Sequence) -> Option<Key> {
+    match key {
+        Key::Image(image) => Some(Key::Image(gather(sequence, |x, y| *image.get_pixel(x, y)))),
+        _ => None,
+    }
+}
+
+/// Gathers pixels at positions of a sequence into a row of an image.
+fn gather<P: Pixel + 'static>(
+    sequence: &Sequence,
+    pixel_at: impl Fn(u32, u32) -> P,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let mut gathered = ImageBuffer::new(sequence.len() as u32, 1);
+    for (pixel, &(x, y)) in gathered.pixels_mut().zip(sequence) {
+        *pixel = pixel_at(x, y);
+    }
+    gathered
+}
+
+/// Returns the distance and the angle from `0.0` (left) to `1.0` of clockwise turn
+/// of a position around the center.
+fn polar_of((cx, cy): (u32, u32), (x, y): (u32, u32)) -> (f32, f32) {
+    let dx = x as f32 - cx as f32;
+    let dy = y as f32 - cy as f32;
+    let turn = (dy.atan2(dx) + PI) / (2.0 * PI);
+    ((dx * dx + dy * dy).sqrt(), turn.rem_euclid(1.0))
+}
+
+fn positions(width: u32, height: u32) -> impl Iterator<Item = (u32, u32)> {
+    (0..height).flat_map(move |y| (0..width).map(move |x| (x, y)))
+}
+
+fn circles(center: (u32, u32), width: u32, height: u32) -> Vec<Sequence> {
+    let mut polar: Vec<_> = positions(width, height)
+        .map(|position| {
+            let (distance, turn) = polar_of(center, position);
+            (distance.round() as u32, turn, position)
+        })
+        .collect();
+    polar.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+    let mut sequences: Vec<Sequence> = Vec::new();
+    let mut radius = None;
+    for (r, _, position) in polar {
+        if radius != Some(r) {
+            sequences.push(Vec::new());
+            radius = Some(r);
+        }
+        sequences.last_mut().unwrap().push(position);
+    }
+    sequences
+}
+
+fn rays((cx, cy): (u32, u32), width: u32, height: u32) -> Vec<Sequence> {
+    let center = (cx.min(width - 1), cy.min(height - 1));
+    let (cx, cy) = (center.0 as f32, center.1 as f32);
+    let (right, bottom) = ((width - 1) as f32, (height - 1) as f32);
+
+    // NOTE: Pixels are grouped by border pixels where lines from the center through them end
+    let mut polar: Vec<_> = positions(width, height)
+        .filter(|&position| position != center)
+        .map(|position| {
+            let (dx, dy) = (position.0 as f32 - cx, position.1 as f32 - cy);
+            let limit = |d: f32, low: f32, high: f32| match d {
+                d if d > 0.0 => high / d,
+                d if d < 0.0 => -low / d,
+                _ => f32::INFINITY,
+            };
+            let t = limit(dx, cx, right - cx).min(limit(dy, cy, bottom - cy));
+            let border = ((cx + dx * t).round() as u32, (cy + dy * t).round() as u32);
+            let (_, turn) = polar_of(center, border);
+            let (distance, _) = polar_of(center, position);
+            (turn, border, distance, position)
+        })
+        .collect();
+    polar.sort_by(|a, b| {
+        (a.0.total_cmp(&b.0))
+            .then(a.1.cmp(&b.1))
+            .then(a.2.total_cmp(&b.2))
+    });
+
+    let mut sequences = vec![vec![center]];
+    let mut ray = None;
+    for (i, (_, border, _, position)) in polar.into_iter().enumerate() {
+        if i > 0 && ray != Some(border) {
+            sequences.push(Vec::new());
+        }
+        ray = Some(border);
+        sequences.last_mut().unwrap().push(position);
+    }
+    sequences
+}
+
+fn spiral(center: (u32, u32), spacing: u32, width: u32, height: u32) -> Sequence {
+    let spacing = spacing.max(1) as f32;
+    let mut polar: Vec<_> = positions(width, height)
+        .map(|position| {
+            let (distance, turn) = polar_of(center, position);
+            // NOTE: The spiral passes the angle at distances of `spacing * (n + turn)`
+            let n = (distance / spacing - turn).round();
+            (n + turn, distance, position)
+        })
+        .collect();
+    polar.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+    polar.into_iter().map(|(_, _, position)| position).collect()
+}
+
+/// Orders all pixels by their indices on a curve.
+fn curve(width: u32, height: u32, index_of: impl Fn(u32, u32) -> u64) -> Sequence {
+    let mut sequence: Vec<_> = positions(width, height).collect();
+    sequence.sort_by_key(|&(x, y)| index_of(x, y));
+    sequence
+}
+
+/// Returns the index of a position on the Hilbert curve over a square of `side`,
+/// a power of two.
+fn hilbert_index(side: u32, mut x: u32, mut y: u32) -> u64 {
+    let mut index = 0;
+    let mut s = side / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as u64;
+        let ry = (y & s > 0) as u64;
+        index += s as u64 * s as u64 * ((3 * rx) ^ ry);
+
+        // NOTE: Quadrants are rotated so the curve is continuous
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
+
+/// Returns the index of a position on the Z-order curve by interleaving bits.
+fn z_index(x: u32, y: u32) -> u64 {
+    let spread = |v: u32| {
+        let mut v = v as u64;
+        v = (v | v << 16) & 0x0000_ffff_0000_ffff;
+        v = (v | v << 8) & 0x00ff_00ff_00ff_00ff;
+        v = (v | v << 4) & 0x0f0f_0f0f_0f0f_0f0f;
+        v = (v | v << 2) & 0x3333_3333_3333_3333;
+        (v | v << 1) & 0x5555_5555_5555_5555
+    };
+    spread(x) | spread(y) << 1
+}
+
+fn flow(field: &RgbImage) -> Vec<Sequence> {
+    let (width, height) = field.dimensions();
+    let mut visited = vec![false; width as usize * height as usize];
+    let index = |(x, y): (u32, u32)| y as usize * width as usize + x as usize;
+
+    let mut sequences = Vec::new();
+    for start in positions(width, height) {
+        if visited[index(start)] {
+            continue;
+        }
+
+        let mut sequence = vec![start];
+        visited[index(start)] = true;
+        let mut position = start;
+        while let Some(next) = step(field, position) {
+            if visited[index(next)] {
+                break;
+            }
+            sequence.push(next);
+            visited[index(next)] = true;
+            position = next;
+        }
+        sequences.push(sequence);
+    }
+    sequences
+}
+
+/// Returns the neighbor pointed by the vector of the flow field at the position.
+fn step(field: &RgbImage, (x, y): (u32, u32)) -> Option<(u32, u32)> {
+    let [r, g, _] = field.get_pixel(x, y).0;
+    let (vx, vy) = (r as f32 - 128.0, g as f32 - 128.0);
+    let length = (vx * vx + vy * vy).sqrt();
+    if length == 0.0 {
+        return None;
+    }
+
+    let dx = (vx / length).round() as i64;
+    let dy = (vy / length).round() as i64;
+    let (x, y) = (x as i64 + dx, y as i64 + dy);
+    if x < 0 || y < 0 || x >= field.width() as i64 || y >= field.height() as i64 {
+        return None;
+    }
+    Some((x as u32, y as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Mode;
+    use image::{GrayImage, Rgb};
+
+    fn assert_covers(sequences: &[Sequence], width: u32, height: u32) {
+        let mut visited: Vec<_> = sequences.iter().flatten().copied().collect();
+        visited.sort_unstable();
+        let expected: Vec<_> = (0..width)
+            .flat_map(|x| (0..height).map(move |y| (x, y)))
+            .collect();
+        assert_eq!(expected, visited);
+        assert!(sequences.iter().all(|sequence| !sequence.is_empty()));
+    }
+
+    #[test]
+    fn test_sequences_of_circles() {
+        let path = Path::Circles { center: (2, 2) };
+        let sequences = sequences_of(&path, 5, 4);
+        assert_covers(&sequences, 5, 4);
+
+        assert_eq!(vec![(2, 2)], sequences[0]);
+        let expected = vec![
+            (1, 2),
+            (1, 1),
+            (2, 1),
+            (3, 1),
+            (3, 2),
+            (3, 3),
+            (2, 3),
+            (1, 3),
+        ];
+        assert_eq!(expected, sequences[1], "clockwise");
+    }
+
+    #[test]
+    fn test_sequences_of_rays() {
+        for center in [(3, 2), (0, 0), (6, 5), (100, 1)] {
+            let sequences = sequences_of(&Path::Rays { center }, 7, 5);
+            assert_covers(&sequences, 7, 5);
+
+            let center = (center.0.min(6), center.1.min(4));
+            assert_eq!(center, sequences[0][0], "from the center");
+            for sequence in &sequences {
+                let distances: Vec<_> = sequence.iter().map(|&p| polar_of(center, p).0).collect();
+                assert!(distances.windows(2).all(|d| d[0] <= d[1]), "outward");
+            }
+        }
+
+        let sequences = sequences_of(&Path::Rays { center: (3, 2) }, 7, 5);
+        assert_eq!(20, sequences.len(), "a ray for each border pixel");
+        assert_eq!(vec![(3, 2), (2, 2), (1, 2), (0, 2)], sequences[0]);
+    }
+
+    #[test]
+    fn test_sequences_of_spiral() {
+        let path = Path::Spiral {
+            center: (3, 3),
+            spacing: 2,
+        };
+        let sequences = sequences_of(&path, 7, 6);
+        assert_eq!(1, sequences.len());
+        assert_covers(&sequences, 7, 6);
+        assert_eq!((3, 3), sequences[0][0], "from the center");
+    }
+
+    #[test]
+    fn test_sequences_of_hilbert() {
+        let sequences = sequences_of(&Path::Hilbert, 8, 8);
+        assert_eq!(1, sequences.len());
+        assert_covers(&sequences, 8, 8);
+        let adjacent =
+            |(a, b): (&(u32, u32), &(u32, u32))| a.0.abs_diff(b.0) + a.1.abs_diff(b.1) == 1;
+        let sequence = &sequences[0];
+        assert!(
+            sequence.iter().zip(&sequence[1..]).all(adjacent),
+            "continuous"
+        );
+        assert_eq!(vec![(0, 0), (0, 1), (1, 1), (1, 0)], sequence[..4]);
+
+        assert_covers(&sequences_of(&Path::Hilbert, 5, 3), 5, 3);
+    }
+
+    #[test]
+    fn test_sequences_of_z_order() {
+        let sequences = sequences_of(&Path::ZOrder, 4, 3);
+        assert_covers(&sequences, 4, 3);
+        let expected = vec![(0, 0), (1, 0), (0, 1), (1, 1), (2, 0), (3, 0)];
+        assert_eq!(expected, sequences[0][..6]);
+    }
+
+    #[test]
+    fn test_sequences_of_serpentine() {
+        let sequences = sequences_of(&Path::Serpentine, 3, 2);
+        let expected = vec![(0, 0), (1, 0), (2, 0), (2, 1), (1, 1), (0, 1)];
+        assert_eq!(vec![expected], sequences);
+    }
+
+    #[test]
+    fn test_sequences_of_flow() {
+        // NOTE: Vectors point right in the top row and down elsewhere
+        let field = RgbImage::from_fn(3, 3, |_, y| match y {
+            0 => Rgb([255, 128, 0]),
+            _ => Rgb([128, 255, 0]),
+        });
+        let sequences = sequences_of(&Path::Flow(field), 3, 3);
+        assert_covers(&sequences, 3, 3);
+
+        assert_eq!(
+            vec![(0, 0), (1, 0), (2, 0)],
+            sequences[0],
+            "ends at the border"
+        );
+        assert_eq!(vec![(0, 1), (0, 2)], sequences[1]);
+        assert_eq!(vec![(1, 1), (1, 2)], sequences[2]);
+        assert_eq!(vec![(2, 1), (2, 2)], sequences[3]);
+    }
+
+    #[test]
+    fn test_gather_options() {
+        let image = GrayImage::from_raw(2, 2, vec![10, 20, 30, 40]).unwrap();
+        let original = Options {
+            variation: Variation::Map {
+                map: image.clone(),
+                end: Some(Mode::Brightness(255)),
+            },
+            key: Key::Image(image),
+            tie: TieBreak::Key(Key::Hue),
+            ..Default::default()
+        };
+        let mut options = sequence_options(&original);
+        gather_options(&mut options, &original, &vec![(1, 1), (0, 0)], 0);
+
+        let gathered = GrayImage::from_raw(2, 1, vec![40, 10]).unwrap();
+        let expected = Options {
+            direction: Direction::Row,
+            variation: Variation::Map {
+                map: gathered.clone(),
+                end: Some(Mode::Brightness(255)),
+            },
+            key: Key::Image(gathered),
+            tie: TieBreak::Key(Key::Hue),
+            ..Default::default()
+        };
+        assert_eq!(expected, options);
+    }
+
+    #[test]
+    fn test_gather_options_gradients() {
+        let sequence = vec![(2, 0), (1, 0), (0, 0)];
+        let original = Options {
+            variation: Variation::Linear {
+                from: (0, 0),
+                to: (2, 0),
+                end: Mode::Brightness(255),
+            },
+            ..Default::default()
+        };
+        let mut options = sequence_options(&original);
+        gather_options(&mut options, &original, &sequence, 0);
+        let expected = Variation::Map {
+            map: GrayImage::from_raw(3, 1, vec![255, 128, 0]).unwrap(),
+            end: Some(Mode::Brightness(255)),
+        };
+        assert_eq!(expected, options.variation);
+
+        let original = Options {
+            variation: Variation::Radial {
+                center: (0, 0),
+                radius: 2,
+                end: Mode::Brightness(255),
+            },
+            ..Default::default()
+        };
+        gather_options(&mut options, &original, &sequence, 0);
+        assert_eq!(expected, options.variation);
+    }
+
+    #[test]
+    fn test_gather_options_jitter() {
+        let original = Options {
+            variation: Variation::Jitter {
+                amount: 10,
+                seed: 0b1100,
+            },
+            ..Default::default()
+        };
+        let mut options = sequence_options(&original);
+        gather_options(&mut options, &original, &vec![(0, 0)], 0b1010);
+        let expected = Variation::Jitter {
+            amount: 10,
+            seed: 0b0110,
+        };
+        assert_eq!(expected, options.variation, "seeded by the index");
+    }
+
+    #[test]
+    fn test_sequences_of_empty() {
+        let path = Path::Circles { center: (0, 0) };
+        assert!(sequences_of(&path, 0, 3).is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Token to cancel sorting, which can be shared with other threads.
+///
+/// # Example
+///
+/// ```
+/// # use asdf_pixel_sort::Cancellation;
+/// let cancellation = Cancellation::new();
+/// let token = cancellation.clone();
+/// std::thread::spawn(move || token.cancel()).join().unwrap();
+/// assert!(cancellation.is_cancelled());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Cancellation(Arc<AtomicBool>);
+
+impl Cancellation {
+    /// Creates a new token which isn't cancelled yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests to cancel sorting. Sorting stops before the next line.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if cancellation is requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Error returned when sorting is cancelled by [`Cancellation`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "sorting was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancellation() {
+        let cancellation = Cancellation::new();
+        assert!(!cancellation.is_cancelled());
+
+        let token = cancellation.clone();
+        token.cancel();
+        assert!(cancellation.is_cancelled(), "shared with clones");
+    }
+}
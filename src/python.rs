@@ -0,0 +1,223 @@
+use image::{RgbImage, RgbaImage};
+use numpy::PyReadwriteArray3;
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{sort_rgba, sort_with_options, Alpha, Direction, Mode, Options, PColor};
+
+/// Threshold of a mode given from Python, a brightness or a color.
+#[derive(Debug, FromPyObject)]
+enum Threshold {
+    Value(u8),
+    Color(u8, u8, u8),
+}
+
+/// Sorts pixels of an `uint8` array of `(height, width, 3)` or `(height, width, 4)` in place.
+///
+/// `mode` is `"brightness"`, `"black"`, `"white"` or `"edge"`, and `threshold` is an integer for
+/// brightness and edge modes or a tuple of red, green and blue for black and white modes.
+/// `alpha` is `"ignore"`, `"break"`, `"exclude"` or `"premultiply"` to treat transparent pixels.
+/// Defaults are the same as `Options`. Alpha values move along with pixels.
+#[pyfunction]
+#[pyo3(
+    signature = (array, mode = "brightness", threshold = None, direction = "both", alpha = "ignore")
+)]
+fn sort(
+    py: Python<'_>,
+    mut array: PyReadwriteArray3<'_, u8>,
+    mode: &str,
+    threshold: Option<Threshold>,
+    direction: &str,
+    alpha: &str,
+) -> PyResult<()> {
+    let options = options_of(mode, threshold, direction, alpha).map_err(PyValueError::new_err)?;
+
+    let mut view = array.as_array_mut();
+    let (height, width, channels) = view.dim();
+    if channels != 3 && channels != 4 {
+        return Err(PyValueError::new_err(
+            "Array must have a shape of (height, width, 3) or (height, width, 4)",
+        ));
+    }
+    if width == 0 || height == 0 {
+        return Ok(());
+    }
+
+    let mut packed = Vec::with_capacity(width * height * channels);
+    for pixel in view.rows() {
+        packed.extend(pixel.iter());
+    }
+
+    let (width, height) = (width as u32, height as u32);
+    let sorted = py.detach(|| match channels {
+        3 => {
+            let mut buf = RgbImage::from_raw(width, height, packed).unwrap();
+            sort_with_options(&mut buf, &options);
+            buf.into_raw()
+        }
+        _ => {
+            let mut buf = RgbaImage::from_raw(width, height, packed).unwrap();
+            sort_rgba(&mut buf, &options);
+            buf.into_raw()
+        }
+    });
+
+    let pixels = view.rows_mut().into_iter();
+    for (mut pixel, sorted) in pixels.zip(sorted.chunks_exact(channels)) {
+        for (value, sorted) in pixel.iter_mut().zip(sorted) {
+            *value = *sorted;
+        }
+    }
+
+    Ok(())
+}
+
+/// Builds [`Options`] from arguments of [`sort()`].
+fn options_of(
+    mode: &str,
+    threshold: Option<Threshold>,
+    direction: &str,
+    alpha: &str,
+) -> Result<Options, String> {
+    let mode = match (mode, threshold) {
+        ("brightness", None) => Mode::brightness(),
+        ("edge", None) => Mode::edge(),
+        ("black", None) => Mode::black(),
+        ("white", None) => Mode::white(),
+        ("brightness", Some(Threshold::Value(value))) => Mode::Brightness(value),
+        ("edge", Some(Threshold::Value(value))) => Mode::Edge(value),
+        ("black", Some(Threshold::Color(r, g, b))) => Mode::Black(PColor::new(r, g, b)),
+        ("white", Some(Threshold::Color(r, g, b))) => Mode::White(PColor::new(r, g, b)),
+        ("brightness" | "edge", _) => return Err("Threshold must be an integer".into()),
+        ("black" | "white", _) => return Err("Threshold must be a tuple of RGB".into()),
+        (mode, _) => return Err(format!("Unknown mode: {}", mode)),
+    };
+
+    let direction = match direction {
+        "both" => Direction::Both,
+        "column" => Direction::Column,
+        "row" => Direction::Row,
+        direction => return Err(format!("Unknown direction: {}", direction)),
+    };
+
+    let alpha = match alpha {
+        "ignore" => Alpha::Ignore,
+        "break" => Alpha::Break,
+        "exclude" => Alpha::Exclude,
+        "premultiply" => Alpha::Premultiply,
+        alpha => return Err(format!("Unknown alpha: {}", alpha)),
+    };
+
+    Ok(Options {
+        mode,
+        direction,
+        alpha,
+        ..Default::default()
+    })
+}
+
+/// Python module of ASDF pixel sorting.
+#[pymodule]
+fn asdf_pixel_sort(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(sort, m)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use numpy::{PyArray3, PyArrayMethods};
+
+    /// Sorts a row of pixels in an array by rows with defaults, returning the sorted array.
+    fn sorted_row(pixels: &[&[u8]], alpha: &str) -> PyResult<Vec<u8>> {
+        Python::initialize();
+        Python::attach(|py| {
+            let row = pixels.iter().map(|pixel| pixel.to_vec()).collect();
+            let array = PyArray3::from_vec3(py, &[row]).unwrap();
+            sort(py, array.readwrite(), "brightness", None, "row", alpha)?;
+            Ok(array.to_vec().unwrap())
+        })
+    }
+
+    #[test]
+    fn test_sort_rgb() {
+        let pixels: [&[u8]; 4] = [
+            &[255, 255, 255],
+            &[128, 128, 128],
+            &[64, 64, 64],
+            &[0, 0, 0],
+        ];
+        let expected = [128, 128, 128, 255, 255, 255, 64, 64, 64, 0, 0, 0];
+        assert_eq!(expected, sorted_row(&pixels, "ignore").unwrap()[..]);
+    }
+
+    #[test]
+    fn test_sort_rgba() {
+        let pixels: [&[u8]; 4] = [
+            &[255, 255, 255, 10],
+            &[128, 128, 128, 20],
+            &[64, 64, 64, 30],
+            &[0, 0, 0, 40],
+        ];
+        let expected = [
+            128, 128, 128, 20, 255, 255, 255, 10, 64, 64, 64, 30, 0, 0, 0, 40,
+        ];
+        assert_eq!(
+            expected,
+            sorted_row(&pixels, "ignore").unwrap()[..],
+            "alpha moves along"
+        );
+    }
+
+    #[test]
+    fn test_sort_shape() {
+        let pixels: [&[u8]; 2] = [&[255, 255], &[128, 128]];
+        assert!(sorted_row(&pixels, "ignore").is_err());
+    }
+
+    #[test]
+    fn test_options_of() {
+        assert_eq!(
+            Ok(Options::default()),
+            options_of("brightness", None, "both", "ignore")
+        );
+
+        let expected = Options {
+            mode: Mode::Black(PColor::new(1, 2, 3)),
+            direction: Direction::Row,
+            alpha: Alpha::Premultiply,
+            ..Default::default()
+        };
+        let threshold = Some(Threshold::Color(1, 2, 3));
+        assert_eq!(
+            Ok(expected),
+            options_of("black", threshold, "row", "premultiply")
+        );
+
+        let threshold = Some(Threshold::Value(30));
+        assert_eq!(
+            Ok(Mode::Edge(30)),
+            options_of("edge", threshold, "both", "ignore").map(|o| o.mode)
+        );
+    }
+
+    #[test]
+    fn test_options_of_errors() {
+        let threshold = Some(Threshold::Value(30));
+        assert!(
+            options_of("white", threshold, "both", "ignore").is_err(),
+            "mismatched"
+        );
+        assert!(
+            options_of("gray", None, "both", "ignore").is_err(),
+            "unknown mode"
+        );
+        assert!(
+            options_of("brightness", None, "diagonal", "ignore").is_err(),
+            "unknown direction"
+        );
+        assert!(
+            options_of("brightness", None, "both", "keep").is_err(),
+            "unknown alpha"
+        );
+    }
+}
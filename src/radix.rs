@@ -0,0 +1,164 @@
+use image::Rgba;
+
+/// Pixel with its sorting key.
+pub(crate) type Entry<P = Rgba<u8>> = (u64, P);
+
+/// Lines up to this length are sorted by insertion sort.
+const INSERTION_SORT_MAX: usize = 48;
+
+/// Sorts entries by keys stably with LSD radix sort.
+///
+/// `scratch` is a working buffer which can be reused across calls to avoid allocations.
+/// Passes for bytes which are the same in all keys are skipped.
+pub(crate) fn radix_sort<P: Copy>(entries: &mut [Entry<P>], scratch: &mut Vec<Entry<P>>) {
+    let len = entries.len();
+    if len <= INSERTION_SORT_MAX {
+        insertion_sort(entries);
+        return;
+    }
+
+    let (mut all, mut any) = (u64::MAX, 0);
+    for (key, _) in entries.iter() {
+        all &= key;
+        any |= key;
+    }
+    let varying = all ^ any;
+
+    scratch.clear();
+    scratch.resize(len, entries[0]);
+
+    let mut sorted_in_scratch = false;
+    for shift in (0..64).step_by(8) {
+        if (varying >> shift) & 0xff == 0 {
+            continue; // all keys have the same byte
+        }
+
+        let src: &[Entry<P>] = if sorted_in_scratch { scratch } else { entries };
+        let mut offsets = [0; 256];
+        for (key, _) in src {
+            offsets[(key >> shift) as usize & 0xff] += 1;
+        }
+        let mut sum = 0;
+        for offset in offsets.iter_mut() {
+            let count = *offset;
+            *offset = sum;
+            sum += count;
+        }
+
+        if sorted_in_scratch {
+            scatter(scratch, entries, shift, &mut offsets);
+        } else {
+            scatter(entries, scratch, shift, &mut offsets);
+        }
+        sorted_in_scratch = !sorted_in_scratch;
+    }
+
+    if sorted_in_scratch {
+        entries.copy_from_slice(scratch);
+    }
+}
+
+/// Moves entries to buckets of the byte at `shift`.
+fn scatter<P: Copy>(
+    src: &[Entry<P>],
+    dst: &mut [Entry<P>],
+    shift: usize,
+    offsets: &mut [usize; 256],
+) {
+    for entry in src {
+        let bucket = (entry.0 >> shift) as usize & 0xff;
+        dst[offsets[bucket]] = *entry;
+        offsets[bucket] += 1;
+    }
+}
+
+/// Sorts entries by keys stably with insertion sort.
+fn insertion_sort<P>(entries: &mut [Entry<P>]) {
+    for i in 1..entries.len() {
+        let mut j = i;
+        while 0 < j && entries[i].0 < entries[j - 1].0 {
+            j -= 1;
+        }
+        entries[j..=i].rotate_right(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::Rgb;
+
+    /// Generates entries with pseudo-random keys, where pixels record the original positions.
+    fn entries_of(len: usize, mask: u64) -> Vec<Entry<Rgb<u8>>> {
+        let mut z = 0x1234_5678_u64;
+        (0..len)
+            .map(|i| {
+                z ^= z << 13;
+                z ^= z >> 7;
+                z ^= z << 17;
+                let [a, b, ..] = (i as u32).to_le_bytes();
+                (z & mask, Rgb([a, b, 0]))
+            })
+            .collect()
+    }
+
+    fn assert_sorted(len: usize, mask: u64) {
+        let mut expected = entries_of(len, mask);
+        expected.sort_by_key(|(key, _)| *key);
+
+        let mut actual = entries_of(len, mask);
+        radix_sort(&mut actual, &mut Vec::new());
+
+        assert_eq!(expected, actual, "len={}, mask={:#x}", len, mask);
+    }
+
+    #[test]
+    fn test_radix_sort() {
+        for &len in &[0, 1, 2, 47, 48, 49, 100, 1000] {
+            assert_sorted(len, u64::MAX);
+            assert_sorted(len, 0xff);
+            assert_sorted(len, 0x0f00_0000_0000_0000);
+            assert_sorted(len, 0);
+        }
+    }
+
+    #[test]
+    fn test_radix_sort_stable() {
+        assert_sorted(1000, 0x3);
+        assert_sorted(1000, 0x3_0000_0000);
+    }
+
+    #[test]
+    fn test_radix_sort_reuse_scratch() {
+        let mut scratch = Vec::new();
+        for &len in &[1000, 10, 500] {
+            let mut expected = entries_of(len, 0xffff);
+            expected.sort_by_key(|(key, _)| *key);
+
+            let mut actual = entries_of(len, 0xffff);
+            radix_sort(&mut actual, &mut scratch);
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_insertion_sort() {
+        let mut entries = vec![
+            (3, Rgb([0, 0, 0])),
+            (1, Rgb([1, 0, 0])),
+            (3, Rgb([2, 0, 0])),
+            (0, Rgb([3, 0, 0])),
+            (1, Rgb([4, 0, 0])),
+        ];
+        insertion_sort(&mut entries);
+        let expected = vec![
+            (0, Rgb([3, 0, 0])),
+            (1, Rgb([1, 0, 0])),
+            (1, Rgb([4, 0, 0])),
+            (3, Rgb([0, 0, 0])),
+            (3, Rgb([2, 0, 0])),
+        ];
+        assert_eq!(expected, entries);
+    }
+}
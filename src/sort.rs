@@ -1,8 +1,15 @@
-use image::RgbImage;
+use image::{GrayImage, Rgb, RgbImage, Rgba, RgbaImage};
 
 use crate::{
-    line_sorter::{sort_column, sort_row},
-    Options,
+    blend::blend_pixels,
+    edge::edge_map,
+    key::Keys,
+    line_sorter::LineSorter,
+    path::{gather_options, sequence_options, sequences_of, Sequence},
+    progress::{Cancellation, Cancelled},
+    transfer::Decoder,
+    transpose::{transpose, transpose_options},
+    Channels, Direction, Guide, Key, Mode, Options, Path, TieBreak, Variation,
 };
 
 /// Sorts pixels in the given image with default options
@@ -11,16 +18,396 @@ pub fn sort(buf: &mut RgbImage) {
 }
 
 /// Sorts pixels in the given image with options
+///
+/// # Panics
+///
+/// Panics if the threshold map of [`Variation::Map`], the key image of [`Key::Image`]
+/// (including one of [`TieBreak::Key`]), the guide image of [`Guide::Image`] or the mask of
+/// [`Options::blend`] has different dimensions from the image.
 pub fn sort_with_options(buf: &mut RgbImage, options: &Options) {
+    sort_lines(buf, None, options, &mut |_| true);
+}
+
+/// Sorts pixels in the given image with options, reporting progress and checking cancellation
+///
+/// `progress` is called with a fraction of sorted lines between `0.0` to `1.0` each time a row
+/// or a column is sorted. `cancellation` is checked between lines, and the image is left
+/// partially sorted if cancelled.
+///
+/// # Errors
+///
+/// Returns [`Cancelled`] if sorting is cancelled by `cancellation`.
+///
+/// # Panics
+///
+/// Panics as [`sort_with_options()`] does.
+///
+/// # Example
+///
+/// ```
+/// # use asdf_pixel_sort::{sort_with_progress, Cancellation, Options};
+/// # let mut buf = image::RgbImage::new(16, 16);
+/// let cancellation = Cancellation::new();
+/// let result = sort_with_progress(
+///     &mut buf,
+///     &Options::default(),
+///     |fraction| println!("{:.0}%", fraction * 100.0),
+///     &cancellation,
+/// );
+/// assert!(result.is_ok());
+/// ```
+pub fn sort_with_progress(
+    buf: &mut RgbImage,
+    options: &Options,
+    mut progress: impl FnMut(f32),
+    cancellation: &Cancellation,
+) -> Result<(), Cancelled> {
+    if cancellation.is_cancelled() {
+        return Err(Cancelled);
+    }
+
+    let completed = sort_lines(buf, None, options, &mut |fraction| {
+        progress(fraction);
+        !cancellation.is_cancelled()
+    });
+
+    if completed {
+        Ok(())
+    } else {
+        Err(Cancelled)
+    }
+}
+
+/// Sorts pixels in the given RGBA image with options
+///
+/// Pixels are sorted by their RGB values, alpha values move along with their pixels, and
+/// transparent pixels are treated by [`Options::alpha`].
+///
+/// # Panics
+///
+/// Panics as [`sort_with_options()`] does.
+///
+/// # Example
+///
+/// ```
+/// # use asdf_pixel_sort::{sort_rgba, Alpha, Options};
+/// # let mut buf = image::RgbaImage::new(16, 16);
+/// let options = Options {
+///     alpha: Alpha::Break,
+///     ..Default::default()
+/// };
+/// sort_rgba(&mut buf, &options);
+/// ```
+pub fn sort_rgba(buf: &mut RgbaImage, options: &Options) {
+    let (width, height) = buf.dimensions();
+    let mut rgb = RgbImage::new(width, height);
+    let mut alpha = GrayImage::new(width, height);
+    for ((rgba, rgb), a) in buf.pixels().zip(rgb.pixels_mut()).zip(alpha.iter_mut()) {
+        let [r, g, b, alpha] = rgba.0;
+        *rgb = Rgb([r, g, b]);
+        *a = alpha;
+    }
+
+    sort_lines(&mut rgb, Some(&mut alpha), options, &mut |_| true);
+
+    for ((rgba, rgb), a) in buf.pixels_mut().zip(rgb.pixels()).zip(alpha.iter()) {
+        let [r, g, b] = rgb.0;
+        *rgba = Rgba([r, g, b, *a]);
+    }
+}
+
+/// Sorts Processing-style packed ARGB pixels, like `pixels[]` of a sketch, with options
+///
+/// Pixels are sorted as [`sort_rgba()`] does.
+///
+/// # Panics
+///
+/// Panics if the length of `pixels` is not `width * height`, or as [`sort_with_options()`] does.
+///
+/// # Example
+///
+/// ```
+/// # use asdf_pixel_sort::{sort_raw_pixels, Options, PColor};
+/// let mut pixels = vec![PColor::new(255, 255, 255).as_raw(); 16 * 16];
+/// sort_raw_pixels(&mut pixels, 16, 16, &Options::default());
+/// ```
+pub fn sort_raw_pixels(pixels: &mut [i32], width: u32, height: u32, options: &Options) {
+    assert_eq!(
+        width as usize * height as usize,
+        pixels.len(),
+        "pixels must have the width and height"
+    );
+
+    let mut rgb = Vec::with_capacity(pixels.len() * 3);
+    let mut alpha = Vec::with_capacity(pixels.len());
+    for pixel in pixels.iter() {
+        let [a, r, g, b] = pixel.to_be_bytes();
+        rgb.extend_from_slice(&[r, g, b]);
+        alpha.push(a);
+    }
+    let mut buf = RgbImage::from_raw(width, height, rgb).unwrap();
+    let mut alpha = GrayImage::from_raw(width, height, alpha).unwrap();
+
+    sort_lines(&mut buf, Some(&mut alpha), options, &mut |_| true);
+
+    for ((pixel, rgb), a) in pixels.iter_mut().zip(buf.pixels()).zip(alpha.iter()) {
+        let [r, g, b] = rgb.0;
+        *pixel = i32::from_be_bytes([*a, r, g, b]);
+    }
+}
+
+/// Sorts pixels in the given image with options, calling `observe` with a fraction of sorted
+/// lines after each line. Returns `false` if stopped by `observe` returning `false`.
+///
+/// Alpha values in `alpha` of the same dimensions are moved along with pixels if given.
+pub(crate) fn sort_lines(
+    buf: &mut RgbImage,
+    mut alpha: Option<&mut GrayImage>,
+    options: &Options,
+    observe: &mut dyn FnMut(f32) -> bool,
+) -> bool {
+    assert_dimensions(options, |dimensions| dimensions == buf.dimensions());
+
+    let original = match &options.guide {
+        Guide::Original => Some(buf.clone()),
+        _ if !options.blend.is_noop() => Some(buf.clone()),
+        _ => None,
+    };
+    let guide = match &options.guide {
+        Guide::Target => None,
+        Guide::Original => original.as_ref(),
+        Guide::Image(image) => Some(image),
+    };
+
+    let decoder = Decoder::new(&options.transfer);
+
+    let sequences = match &options.direction {
+        Direction::Path(path) => sequences_of(path, buf.width(), buf.height()),
+        _ => Vec::new(),
+    };
+
+    let lines = |has: bool, count: u32| if has { count } else { 0 };
+    let total = lines(options.direction.has_column(), buf.width())
+        + lines(options.direction.has_row(), buf.height())
+        + sequences.len() as u32;
+    let mut done = 0;
+    let mut next = || {
+        done += 1;
+        observe(done as f32 / total as f32)
+    };
+
+    // NOTE: Columns are sorted as rows of the transposed image to walk on contiguous memory
     if options.direction.has_column() {
-        for col in 0..buf.width() {
-            sort_column(buf, col, options);
+        let prepared = prepare(guide.unwrap_or(buf), decoder.as_ref(), options);
+        let guide = prepared.as_ref().or(guide).map(transpose);
+        let options = transpose_options(options);
+        let keys = Keys::new(&options.key, &options.tie, decoder.as_ref());
+        let mut transposed = transpose(buf);
+        let mut transposed_alpha = alpha.as_deref().map(transpose);
+        let completed = sort_image_rows(
+            &mut transposed,
+            transposed_alpha.as_mut(),
+            guide.as_ref(),
+            &keys,
+            &options,
+            &mut next,
+        );
+        *buf = transpose(&transposed);
+        if let (Some(alpha), Some(transposed)) = (alpha.as_deref_mut(), transposed_alpha) {
+            *alpha = transpose(&transposed);
+        }
+        if !completed {
+            return false;
         }
     }
 
     if options.direction.has_row() {
-        for row in 0..buf.height() {
-            sort_row(buf, row, options);
+        let prepared = prepare(guide.unwrap_or(buf), decoder.as_ref(), options);
+        let guide = prepared.as_ref().or(guide);
+        let keys = Keys::new(&options.key, &options.tie, decoder.as_ref());
+        if !sort_image_rows(buf, alpha.as_deref_mut(), guide, &keys, options, &mut next) {
+            return false;
+        }
+    }
+
+    if !sequences.is_empty() {
+        let prepared = prepare(guide.unwrap_or(buf), decoder.as_ref(), options);
+        let guide = prepared.as_ref().or(guide);
+        if !sort_image_sequences(
+            buf,
+            alpha,
+            guide,
+            &sequences,
+            options,
+            decoder.as_ref(),
+            &mut next,
+        ) {
+            return false;
+        }
+    }
+
+    if let (false, Some(original)) = (options.blend.is_noop(), &original) {
+        let mask = options.blend.mask.as_ref().map(|mask| &mask.as_raw()[..]);
+        blend_pixels(buf, original, mask, &options.blend, decoder.as_ref());
+    }
+
+    true
+}
+
+/// Asserts images in the options have dimensions accepted by `matches`.
+pub(crate) fn assert_dimensions(options: &Options, matches: impl Fn((u32, u32)) -> bool) {
+    if let Variation::Map { map, .. } = &options.variation {
+        assert!(
+            matches(map.dimensions()),
+            "Threshold map must have the same dimensions with the image"
+        );
+    }
+
+    for key in [Some(&options.key), secondary_key_of(&options.tie)] {
+        if let Some(Key::Image(image)) = key {
+            assert!(
+                matches(image.dimensions()),
+                "Key image must have the same dimensions with the image"
+            );
+        }
+    }
+
+    if let Guide::Image(image) = &options.guide {
+        assert!(
+            matches(image.dimensions()),
+            "Guide image must have the same dimensions with the image"
+        );
+    }
+
+    if let Direction::Path(Path::Flow(field)) = &options.direction {
+        assert!(
+            matches(field.dimensions()),
+            "Flow field must have the same dimensions with the image"
+        );
+    }
+
+    if let Some(mask) = &options.blend.mask {
+        assert!(
+            matches(mask.dimensions()),
+            "Blend mask must have the same dimensions with the image"
+        );
+    }
+}
+
+/// Sorts all rows of the image, calling `next` after each row to continue or not.
+fn sort_image_rows(
+    buf: &mut RgbImage,
+    alpha: Option<&mut GrayImage>,
+    guide: Option<&RgbImage>,
+    keys: &Keys,
+    options: &Options,
+    next: &mut dyn FnMut() -> bool,
+) -> bool {
+    let width = buf.width() as usize;
+    let stride = width * 3;
+    let mut sorter = LineSorter::new(options);
+    let mut alpha_rows = alpha.map(|alpha| alpha.chunks_exact_mut(width));
+    for (y, row) in buf.chunks_exact_mut(stride).enumerate() {
+        let alpha = alpha_rows.as_mut().and_then(Iterator::next);
+        let guide = guide.map(|guide| &guide.as_raw()[y * stride..(y + 1) * stride]);
+        sorter.sort_row(keys, row, alpha, guide, y as u32);
+        if !next() {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Sorts pixels along sequences, gathering each sequence as the row `0` for [`LineSorter`]
+/// with spatial options gathered along it.
+fn sort_image_sequences(
+    buf: &mut RgbImage,
+    mut alpha: Option<&mut GrayImage>,
+    guide: Option<&RgbImage>,
+    sequences: &[Sequence],
+    original: &Options,
+    decoder: Option<&Decoder>,
+    next: &mut dyn FnMut() -> bool,
+) -> bool {
+    let mut options = sequence_options(original);
+    let mut row = Vec::new();
+    let mut guide_row = Vec::new();
+    let mut alpha_row = Vec::new();
+    for (index, sequence) in sequences.iter().enumerate() {
+        gather_options(&mut options, original, sequence, index);
+        let keys = Keys::new(&options.key, &options.tie, decoder);
+
+        row.clear();
+        row.extend(sequence.iter().flat_map(|&(x, y)| buf.get_pixel(x, y).0));
+        let guide = guide.map(|guide| {
+            guide_row.clear();
+            guide_row.extend(sequence.iter().flat_map(|&(x, y)| guide.get_pixel(x, y).0));
+            &guide_row[..]
+        });
+        let mut alpha_values = alpha.as_deref().map(|alpha| {
+            alpha_row.clear();
+            alpha_row.extend(sequence.iter().map(|&(x, y)| alpha.get_pixel(x, y).0[0]));
+            &mut alpha_row[..]
+        });
+
+        LineSorter::new(&options).sort_row(&keys, &mut row, alpha_values.as_deref_mut(), guide, 0);
+
+        for (&(x, y), pixel) in sequence.iter().zip(row.chunks_exact(3)) {
+            buf.get_pixel_mut(x, y).0.copy_from_slice(pixel);
+        }
+        if let (Some(alpha), Some(alpha_values)) = (alpha.as_deref_mut(), alpha_values) {
+            for (&(x, y), &a) in sequence.iter().zip(alpha_values.iter()) {
+                alpha.get_pixel_mut(x, y).0[0] = a;
+            }
+        }
+
+        if !next() {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// Returns a secondary key of the tie-breaking if exists.
+fn secondary_key_of(tie: &TieBreak) -> Option<&Key> {
+    match tie {
+        TieBreak::Key(key) => Some(key),
+        _ => None,
+    }
+}
+
+/// Prepares an image to detect intervals on if the guide needs to be decoded or
+/// to be converted to an edge map.
+pub(crate) fn prepare(
+    guide: &RgbImage,
+    decoder: Option<&Decoder>,
+    options: &Options,
+) -> Option<RgbImage> {
+    let decoded = decoder.map(|decoder| decoder.decode_image(guide));
+    if !options.detects_edges() {
+        return decoded;
+    }
+
+    let source = decoded.as_ref().unwrap_or(guide);
+    match &options.channels {
+        Channels::Joint => Some(edge_map(source)),
+        Channels::Separate(modes) => {
+            // NOTE: Planes of other modes are kept to detect intervals on them as they are
+            let mut prepared = source.clone();
+            for (channel, mode) in modes.iter().enumerate() {
+                if !matches!(mode, Mode::Edge(_)) {
+                    continue;
+                }
+                let plane = RgbImage::from_fn(source.width(), source.height(), |x, y| {
+                    Rgb([source.get_pixel(x, y)[channel]; 3])
+                });
+                for (pixel, edge) in prepared.pixels_mut().zip(edge_map(&plane).pixels()) {
+                    pixel[channel] = edge[0];
+                }
+            }
+            Some(prepared)
         }
     }
 }
@@ -28,8 +415,9 @@ pub fn sort_with_options(buf: &mut RgbImage, options: &Options) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{Direction, Mode};
+    use crate::{Alpha, Blend, BlendMode, PColor, Transfer};
     use helper::*;
+    use image::{GrayImage, Luma};
 
     #[test]
     fn test_sort() {
@@ -75,4 +463,719 @@ mod tests {
 
         assert_sort_with_options!("p1", "row", &options);
     }
+
+    #[test]
+    fn test_sort_with_options_linear() {
+        let options = Options {
+            variation: Variation::Linear {
+                from: (0, 0),
+                to: (700, 0),
+                end: Mode::Brightness(200),
+            },
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "linear", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_jitter() {
+        let options = Options {
+            variation: Variation::Jitter {
+                amount: 40,
+                seed: 0,
+            },
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "jitter", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_map() {
+        let map = GrayImage::from_fn(701, 489, |x, y| image::Luma([((x + y) % 256) as u8]));
+        let options = Options {
+            mode: Mode::Brightness(0),
+            variation: Variation::Map {
+                map,
+                end: Some(Mode::Brightness(255)),
+            },
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "map", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_map_levels() {
+        let sorted = |mode: Mode, variation: Variation| {
+            let mut buf = image::open("tests/p1.bmp").unwrap().to_rgb8();
+            let options = Options {
+                mode,
+                variation,
+                ..Default::default()
+            };
+            sort_with_options(&mut buf, &options);
+            buf
+        };
+        let map = |value: u8, end: u8| Variation::Map {
+            map: GrayImage::from_pixel(701, 489, image::Luma([value])),
+            end: Some(Mode::Brightness(end)),
+        };
+
+        let expected = sorted(Mode::Brightness(80), Variation::Uniform);
+        let actual = sorted(Mode::Brightness(0), map(80, 255));
+        assert!(expected == actual, "map values as thresholds");
+
+        let direct = Variation::Map {
+            map: GrayImage::from_pixel(701, 489, image::Luma([80])),
+            end: None,
+        };
+        let actual = sorted(Mode::Brightness(200), direct);
+        assert!(expected == actual, "map values as thresholds without end");
+
+        let expected = sorted(Mode::Brightness(150), Variation::Uniform);
+        let actual = sorted(Mode::Brightness(100), map(128, 200));
+        assert!(expected == actual, "map values blending thresholds");
+    }
+
+    #[test]
+    fn test_sort_with_options_map_other_kind() {
+        let sorted = |mode: Mode, channels: Channels, variation: Variation| {
+            let mut buf = image::open("tests/p1.bmp").unwrap().to_rgb8();
+            let options = Options {
+                mode,
+                variation,
+                channels,
+                ..Default::default()
+            };
+            sort_with_options(&mut buf, &options);
+            buf
+        };
+        let map = Variation::Map {
+            map: GrayImage::from_pixel(701, 489, image::Luma([128])),
+            end: Some(Mode::Brightness(200)),
+        };
+
+        let expected = sorted(Mode::Edge(100), Channels::Joint, Variation::Uniform);
+        let actual = sorted(Mode::Edge(100), Channels::Joint, map.clone());
+        assert!(expected == actual, "edge not blended toward brightness");
+
+        let planes = |value: u8| {
+            Channels::Separate([Mode::Edge(100), Mode::Brightness(value), Mode::Edge(100)])
+        };
+        let expected = sorted(Mode::default(), planes(150), Variation::Uniform);
+        let actual = sorted(Mode::default(), planes(100), map);
+        assert!(expected == actual, "only brightness planes blended");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sort_with_options_map_mismatch() {
+        let options = Options {
+            variation: Variation::Map {
+                map: GrayImage::new(10, 10),
+                end: Some(Mode::Brightness(255)),
+            },
+            ..Default::default()
+        };
+
+        sort_with_options(&mut RgbImage::new(20, 10), &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_key_image() {
+        let image = GrayImage::from_fn(701, 489, |x, y| image::Luma([((x * y) % 251) as u8]));
+        let options = Options {
+            key: Key::Image(image),
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "key_image", &options);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sort_with_options_key_image_mismatch() {
+        let options = Options {
+            key: Key::Image(GrayImage::new(10, 20)),
+            ..Default::default()
+        };
+
+        sort_with_options(&mut RgbImage::new(10, 10), &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_guide_original() {
+        let options = Options {
+            guide: Guide::Original,
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "guide_original", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_guide_original_column() {
+        let options = Options {
+            direction: Direction::Column,
+            guide: Guide::Original,
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "column", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_guide_image() {
+        let guide = RgbImage::from_fn(701, 489, |x, y| {
+            let value = if (x / 32 + y / 32) % 2 == 0 { 255 } else { 0 };
+            image::Rgb([value, value, value])
+        });
+        let options = Options {
+            guide: Guide::Image(guide),
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "guide_image", &options);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sort_with_options_guide_image_mismatch() {
+        let options = Options {
+            guide: Guide::Image(RgbImage::new(10, 20)),
+            ..Default::default()
+        };
+
+        sort_with_options(&mut RgbImage::new(10, 10), &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_edge() {
+        let options = Options {
+            mode: Mode::edge(),
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "edge_default", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_channels_separate() {
+        let options = Options {
+            channels: Channels::separate(Mode::brightness()),
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "channels_separate", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_channels_modes() {
+        let options = Options {
+            channels: Channels::Separate([Mode::edge(), Mode::Brightness(120), Mode::white()]),
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "channels_modes", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_path_circles() {
+        let options = Options {
+            direction: Direction::Path(Path::Circles { center: (350, 200) }),
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "path_circles", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_path_rays() {
+        let options = Options {
+            direction: Direction::rays((350, 200)),
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "path_rays", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_path_spiral() {
+        let options = Options {
+            mode: Mode::edge(),
+            direction: Direction::Path(Path::Spiral {
+                center: (350, 200),
+                spacing: 4,
+            }),
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "path_spiral", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_path_hilbert() {
+        let options = Options {
+            mode: Mode::black(),
+            direction: Direction::Path(Path::Hilbert),
+            key: Key::Hue,
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "path_hilbert", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_path_z_order() {
+        let options = Options {
+            mode: Mode::white(),
+            direction: Direction::Path(Path::ZOrder),
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "path_z_order", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_path_flow() {
+        // NOTE: Vectors swirl around the center
+        let field = RgbImage::from_fn(701, 489, |x, y| {
+            let (dx, dy) = (x as f32 - 350.0, y as f32 - 244.0);
+            let length = (dx * dx + dy * dy).sqrt().max(1.0);
+            image::Rgb([
+                (128.0 - dy / length * 127.0) as u8,
+                (128.0 + dx / length * 127.0) as u8,
+                0,
+            ])
+        });
+        let options = Options {
+            direction: Direction::Path(Path::Flow(field)),
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "path_flow", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_path_alpha() {
+        let options = Options {
+            direction: Direction::Path(Path::Circles { center: (1, 1) }),
+            ..Default::default()
+        };
+        let mut buf = RgbaImage::from_fn(3, 3, |x, y| {
+            let v = (x * 80 + y * 10) as u8 + 60;
+            Rgba([v, v, v, v])
+        });
+        sort_rgba(&mut buf, &options);
+
+        assert!(buf.pixels().all(|p| p.0[0] == p.0[3]), "alpha moves along");
+    }
+
+    #[test]
+    fn test_sort_with_options_path_linear() {
+        let options = Options {
+            mode: Mode::Brightness(40),
+            direction: Direction::rays((350, 244)),
+            variation: Variation::Linear {
+                from: (0, 0),
+                to: (700, 488),
+                end: Mode::Brightness(200),
+            },
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "path_linear", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_path_key_image() {
+        // NOTE: Keys descend along the serpentine to reverse pixels but the last one of the line
+        let options = Options {
+            mode: Mode::Brightness(0),
+            direction: Direction::Path(Path::Serpentine),
+            key: Key::Image(GrayImage::from_raw(3, 2, vec![50, 40, 30, 0, 10, 20]).unwrap()),
+            ..Default::default()
+        };
+        let mut buf = RgbImage::from_fn(3, 2, |x, y| Rgb([(y * 3 + x) as u8; 3]));
+        sort_with_options(&mut buf, &options);
+
+        let values: Vec<_> = buf.pixels().map(|p| p.0[0]).collect();
+        assert_eq!(vec![4, 5, 2, 3, 0, 1], values);
+    }
+
+    #[test]
+    fn test_sort_with_options_path_singletons() {
+        // NOTE: The top row flows right as a long sequence and other pixels are singletons
+        let (width, height) = (1000, 1000);
+        let field = RgbImage::from_fn(width, height, |_, y| match y {
+            0 => Rgb([255, 128, 0]),
+            _ => Rgb([128, 128, 0]),
+        });
+        let options = Options {
+            mode: Mode::Brightness(0),
+            direction: Direction::Path(Path::Flow(field)),
+            key: Key::Image(GrayImage::from_fn(width, height, |x, _| {
+                Luma([255 - (x / 4) as u8])
+            })),
+            ..Default::default()
+        };
+        let mut buf = RgbImage::from_fn(width, height, |x, _| Rgb([(x / 4) as u8 + 1; 3]));
+        let expected = buf.clone();
+        sort_with_options(&mut buf, &options);
+
+        let top: Vec<_> = (0..width).map(|x| buf.get_pixel(x, 0).0[0]).collect();
+        assert!(
+            top[..999].windows(2).all(|v| v[0] >= v[1]),
+            "sorted by keys"
+        );
+        assert_eq!(250, top[999]);
+        assert_eq!(
+            expected.as_raw()[width as usize * 3..],
+            buf.as_raw()[width as usize * 3..]
+        );
+    }
+
+    #[test]
+    fn test_sort_with_options_feather() {
+        let options = Options {
+            feather: 8,
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "feather", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_blend() {
+        let options = Options {
+            blend: Blend {
+                mode: BlendMode::Screen,
+                opacity: 192,
+                mask: Some(GrayImage::from_fn(701, 489, |x, _| {
+                    image::Luma([(x * 255 / 700) as u8])
+                })),
+            },
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "blend_screen", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_blend_transparent() {
+        let options = Options {
+            blend: Blend {
+                opacity: 0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut buf = image::open("tests/p1.bmp").unwrap().to_rgb8();
+        let original = buf.clone();
+        sort_with_options(&mut buf, &options);
+
+        assert!(original == buf);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sort_with_options_blend_mask_dimensions() {
+        let options = Options {
+            blend: Blend {
+                mask: Some(GrayImage::new(9, 10)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        sort_with_options(&mut RgbImage::new(10, 10), &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_key_lightness() {
+        let options = Options {
+            key: Key::Lightness,
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "key_lightness", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_key_hue() {
+        let options = Options {
+            key: Key::Hue,
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "key_hue", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_transfer_srgb() {
+        let options = Options {
+            transfer: Transfer::Srgb,
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "transfer_srgb", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_transfer_identity() {
+        let options = Options {
+            transfer: Transfer::gamma(1.0),
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "brightness_default", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_tie_reverse_position() {
+        let options = Options {
+            key: Key::Brightness,
+            tie: TieBreak::ReversePosition,
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "tie_reverse_position", &options);
+    }
+
+    #[test]
+    fn test_sort_with_options_tie_key() {
+        let options = Options {
+            key: Key::Brightness,
+            tie: TieBreak::Key(Key::Hue),
+            ..Default::default()
+        };
+
+        assert_sort_with_options!("p1", "tie_key", &options);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sort_with_options_tie_key_image_mismatch() {
+        let options = Options {
+            tie: TieBreak::Key(Key::Image(GrayImage::new(10, 20))),
+            ..Default::default()
+        };
+
+        sort_with_options(&mut RgbImage::new(10, 10), &options);
+    }
+
+    #[test]
+    fn test_sort_with_progress() {
+        let mut fractions = Vec::new();
+        let mut actual = image::open("tests/p1.bmp").unwrap().to_rgb8();
+        let result = sort_with_progress(
+            &mut actual,
+            &Options::default(),
+            |fraction| fractions.push(fraction),
+            &Cancellation::new(),
+        );
+        assert_eq!(Ok(()), result);
+        assert_eq!(701 + 489, fractions.len(), "each column and row");
+        assert!(fractions.windows(2).all(|w| w[0] < w[1]), "increasing");
+        assert_eq!(Some(&1.0), fractions.last());
+
+        let mut expected = image::open("tests/p1.bmp").unwrap().to_rgb8();
+        sort(&mut expected);
+        assert!(expected == actual, "same with sort");
+    }
+
+    #[test]
+    fn test_sort_with_progress_cancel() {
+        let cancellation = Cancellation::new();
+        let mut count = 0;
+        let result = sort_with_progress(
+            &mut RgbImage::new(10, 10),
+            &Options::default(),
+            |_| {
+                count += 1;
+                if count == 3 {
+                    cancellation.cancel();
+                }
+            },
+            &cancellation,
+        );
+        assert_eq!(Err(Cancelled), result);
+        assert_eq!(3, count, "stop before the next line");
+
+        let mut count = 0;
+        let result = sort_with_progress(
+            &mut RgbImage::new(10, 10),
+            &Options::default(),
+            |_| count += 1,
+            &cancellation,
+        );
+        assert_eq!(Err(Cancelled), result);
+        assert_eq!(0, count, "cancelled before sorting");
+    }
+
+    #[test]
+    fn test_sort_raw_pixels() {
+        let options = Options {
+            mode: Mode::black(),
+            ..Default::default()
+        };
+        let mut expected = image::open("tests/p1.bmp").unwrap().to_rgb8();
+        let (width, height) = expected.dimensions();
+
+        // NOTE: Alpha values are marked by colors to check they move with pixels
+        let alpha_of = |[r, g, b]: [u8; 3]| r ^ g.rotate_left(3) ^ b.rotate_left(5);
+        let mut pixels: Vec<_> = expected
+            .pixels()
+            .map(|p| PColor::from(*p).with_alpha(alpha_of(p.0)).as_raw())
+            .collect();
+        sort_raw_pixels(&mut pixels, width, height, &options);
+        sort_with_options(&mut expected, &options);
+
+        for (pixel, expected) in pixels.iter().zip(expected.pixels()) {
+            let color = PColor::from_raw(*pixel);
+            assert_eq!(alpha_of(expected.0), color.alpha);
+            assert_eq!(PColor::from(*expected), color.with_alpha(255));
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sort_raw_pixels_length() {
+        sort_raw_pixels(&mut [0; 5], 2, 3, &Options::default());
+    }
+
+    fn rgba_row(pixels: &[(u8, u8)]) -> RgbaImage {
+        let raw = pixels.iter().flat_map(|&(v, a)| [v, v, v, a]).collect();
+        RgbaImage::from_raw(pixels.len() as u32, 1, raw).unwrap()
+    }
+
+    fn sort_rgba_row(pixels: &[(u8, u8)], alpha: Alpha) -> Vec<(u8, u8)> {
+        let mut buf = rgba_row(pixels);
+        let options = Options {
+            direction: Direction::Row,
+            alpha,
+            ..Default::default()
+        };
+        sort_rgba(&mut buf, &options);
+        buf.pixels().map(|p| (p.0[0], p.0[3])).collect()
+    }
+
+    const RGBA_ROW: [(u8, u8); 9] = [
+        (250, 255),
+        (200, 255),
+        (240, 0),
+        (150, 255),
+        (120, 128),
+        (100, 255),
+        (90, 255),
+        (80, 255),
+        (70, 255),
+    ];
+
+    #[test]
+    fn test_sort_rgba_ignore() {
+        let expected = [
+            (80, 255),
+            (90, 255),
+            (100, 255),
+            (120, 128),
+            (150, 255),
+            (200, 255),
+            (240, 0),
+            (250, 255),
+            (70, 255),
+        ];
+        assert_eq!(expected, sort_rgba_row(&RGBA_ROW, Alpha::Ignore)[..]);
+    }
+
+    #[test]
+    fn test_sort_rgba_break() {
+        let expected = [
+            (200, 255),
+            (250, 255),
+            (240, 0),
+            (80, 255),
+            (90, 255),
+            (100, 255),
+            (120, 128),
+            (150, 255),
+            (70, 255),
+        ];
+        assert_eq!(expected, sort_rgba_row(&RGBA_ROW, Alpha::Break)[..]);
+    }
+
+    #[test]
+    fn test_sort_rgba_exclude() {
+        let expected = [
+            (80, 255),
+            (90, 255),
+            (240, 0),
+            (100, 255),
+            (120, 128),
+            (150, 255),
+            (200, 255),
+            (250, 255),
+            (70, 255),
+        ];
+        assert_eq!(expected, sort_rgba_row(&RGBA_ROW, Alpha::Exclude)[..]);
+    }
+
+    #[test]
+    fn test_sort_rgba_premultiply() {
+        let expected = [
+            (240, 0),
+            (120, 128),
+            (80, 255),
+            (90, 255),
+            (100, 255),
+            (150, 255),
+            (200, 255),
+            (250, 255),
+            (70, 255),
+        ];
+        assert_eq!(expected, sort_rgba_row(&RGBA_ROW, Alpha::Premultiply)[..]);
+    }
+
+    #[test]
+    fn test_sort_rgba_feather_exclude() {
+        let pixels = [
+            (250, 255),
+            (200, 64),
+            (240, 0),
+            (150, 255),
+            (100, 255),
+            (90, 255),
+        ];
+        let mut buf = rgba_row(&pixels);
+        let options = Options {
+            direction: Direction::Row,
+            alpha: Alpha::Exclude,
+            feather: 2,
+            ..Default::default()
+        };
+        sort_rgba(&mut buf, &options);
+
+        let mut alpha: Vec<_> = buf.pixels().map(|p| p.0[3]).collect();
+        assert_eq!(0, alpha[2], "transparent pixels stay");
+        alpha.sort_unstable();
+        assert_eq!(vec![0, 64, 255, 255, 255, 255], alpha, "alpha is not faded");
+        assert_ne!(rgba_row(&pixels), buf, "sorted");
+    }
+
+    #[test]
+    fn test_sort_rgba_opaque() {
+        let options = Options {
+            mode: Mode::white(),
+            alpha: Alpha::Exclude,
+            ..Default::default()
+        };
+        let mut expected = image::open("tests/p1.bmp").unwrap().to_rgb8();
+        let mut actual = image::open("tests/p1.bmp").unwrap().to_rgba8();
+        sort_with_options(&mut expected, &options);
+        sort_rgba(&mut actual, &options);
+
+        assert!(image::DynamicImage::ImageRgba8(actual).to_rgb8() == expected);
+    }
 }
@@ -0,0 +1,704 @@
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::iter::Peekable;
+
+use image::{imageops::crop_imm, RgbImage};
+
+use crate::{
+    blend::blend_pixels,
+    key::Keys,
+    line_sorter::LineSorter,
+    sort::{assert_dimensions, prepare},
+    transfer::Decoder,
+    transpose::{transpose, transpose_options},
+    Direction, Guide, Options,
+};
+
+/// Sorts rows streamed from `rows` with options, holding a few rows in memory at once.
+///
+/// Each row is RGB subpixels of `width` pixels, and sorted rows are emitted in the same order.
+/// Only rows can be sorted in streaming; see [`sort_tiled()`] to sort columns with bounded memory.
+///
+/// # Panics
+///
+/// Panics if the direction isn't [`Direction::Row`], if a row has a different length, or if
+/// images in the options have a different width or fewer rows than streamed.
+///
+/// # Example
+///
+/// ```
+/// # use asdf_pixel_sort::{sort_rows, Direction, Options};
+/// let options = Options {
+///     direction: Direction::Row,
+///     ..Default::default()
+/// };
+/// let rows = vec![vec![255, 255, 255, 64, 64, 64, 128, 128, 128, 0, 0, 0]; 100];
+/// for row in sort_rows(rows, 4, &options) {
+///     assert_eq!(12, row.len());
+/// }
+/// ```
+pub fn sort_rows<I>(rows: I, width: u32, options: &Options) -> SortedRows<'_, I::IntoIter>
+where
+    I: IntoIterator<Item = Vec<u8>>,
+{
+    assert!(
+        options.direction == Direction::Row,
+        "Only rows can be sorted in streaming"
+    );
+
+    SortedRows {
+        rows: rows.into_iter().peekable(),
+        above: None,
+        y: 0,
+        sorter: RowSorter::new(width, options),
+    }
+}
+
+/// Sorts rows of RGB subpixels read from `reader` with options, and writes them to `writer`.
+///
+/// This is [`sort_rows()`] over readers and writers, such as raw pixels of a file.
+///
+/// # Errors
+///
+/// Returns an error if reading or writing fails, or if the last row is incomplete.
+///
+/// # Panics
+///
+/// Panics as [`sort_rows()`] does.
+pub fn sort_rows_from(
+    reader: impl Read,
+    mut writer: impl Write,
+    width: u32,
+    options: &Options,
+) -> io::Result<()> {
+    let mut error = None;
+    let rows = ReadRows {
+        reader,
+        len: width as usize * 3,
+        error: &mut error,
+    };
+
+    for row in sort_rows(rows, width, options) {
+        writer.write_all(&row)?;
+    }
+
+    match error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+/// Sorts raw RGB subpixels of a `width` x `height` image in `stream` in place with options,
+/// holding about `budget` bytes of pixels at once.
+///
+/// Columns are sorted in strips of as many columns as the budget allows, at least one, then rows
+/// are sorted one at a time. The budget covers copies of a strip in memory at once, such as
+/// transposed and prepared ones, but not images in the options, which are held as a whole.
+/// The result is identical to [`sort_with_options()`].
+///
+/// # Errors
+///
+/// Returns an error if reading, writing or seeking the stream fails.
+///
+/// # Panics
+///
+/// Panics if images in the options have different dimensions from the image, or if the guide is
+/// [`Guide::Original`] or [`Options::blend`] blends in [`Direction::Both`] since original rows
+/// are lost by sorting columns, or if the direction is [`Direction::Path`].
+///
+/// [`sort_with_options()`]: crate::sort_with_options
+pub fn sort_tiled<S: Read + Write + Seek>(
+    stream: &mut S,
+    width: u32,
+    height: u32,
+    options: &Options,
+    budget: usize,
+) -> io::Result<()> {
+    assert_dimensions(options, |dimensions| dimensions == (width, height));
+    assert!(
+        !(options.guide == Guide::Original && options.direction == Direction::Both),
+        "Original guide can't be used in both directions with tiled sorting"
+    );
+    assert!(
+        options.blend.is_noop() || options.direction != Direction::Both,
+        "Blending can't be used in both directions with tiled sorting"
+    );
+    assert!(
+        !matches!(options.direction, Direction::Path(_)),
+        "Paths can't be sorted with tiled sorting"
+    );
+
+    if width == 0 || height == 0 {
+        return Ok(());
+    }
+
+    if options.direction.has_column() {
+        sort_strips(stream, width, height, options, budget)?;
+    }
+
+    if options.direction.has_row() {
+        sort_rows_in_place(stream, width, height, options)?;
+    }
+
+    Ok(())
+}
+
+/// Iterator of sorted rows, created by [`sort_rows()`].
+pub struct SortedRows<'a, I: Iterator<Item = Vec<u8>>> {
+    rows: Peekable<I>,
+    above: Option<Vec<u8>>,
+    y: u32,
+    sorter: RowSorter<'a>,
+}
+
+impl<'a, I: Iterator<Item = Vec<u8>>> Iterator for SortedRows<'a, I> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let mut row = self.rows.next()?;
+        let below = self.rows.peek().map(Vec::as_slice);
+
+        let unsorted = self.sorter.needs_neighbors().then(|| row.clone());
+        self.sorter
+            .sort(&mut row, self.y, self.above.as_deref(), below);
+
+        self.above = unsorted;
+        self.y += 1;
+        Some(row)
+    }
+}
+
+/// Sorter of rows one at a time, preparing rows to detect intervals on as a whole image does.
+struct RowSorter<'a> {
+    width: u32,
+    options: &'a Options,
+    decoder: Option<Decoder>,
+    guide: Option<RgbImage>,
+    sorter: LineSorter<'a>,
+}
+
+impl<'a> RowSorter<'a> {
+    fn new(width: u32, options: &'a Options) -> Self {
+        assert_dimensions(options, |(w, _)| w == width);
+
+        let decoder = Decoder::new(&options.transfer);
+        let guide = match &options.guide {
+            Guide::Image(image) => prepare(image, decoder.as_ref(), options),
+            _ => None,
+        };
+
+        Self {
+            width,
+            options,
+            decoder,
+            guide,
+            sorter: LineSorter::new(options),
+        }
+    }
+
+    /// Returns `true` if unsorted rows above and below are needed to prepare a row.
+    fn needs_neighbors(&self) -> bool {
+        self.options.detects_edges() && !matches!(self.options.guide, Guide::Image(_))
+    }
+
+    /// Sorts `row` at `y` with unsorted rows above and below it if exist.
+    fn sort(&mut self, row: &mut [u8], y: u32, above: Option<&[u8]>, below: Option<&[u8]>) {
+        assert!(
+            row.len() == self.width as usize * 3,
+            "Row must have RGB subpixels of the width"
+        );
+
+        let prepared;
+        let guide = match &self.options.guide {
+            Guide::Image(image) => Some(row_of(self.guide.as_ref().unwrap_or(image), y)),
+            _ => {
+                prepared = self.prepare(row, above, below);
+                prepared
+                    .as_ref()
+                    .map(|image| row_of(image, image.height() / 2))
+            }
+        };
+
+        let blend = &self.options.blend;
+        let original = (!blend.is_noop()).then(|| row.to_vec());
+
+        let keys = Keys::new(&self.options.key, &self.options.tie, self.decoder.as_ref());
+        self.sorter.sort_row(&keys, row, None, guide, y);
+
+        if let Some(original) = original {
+            let mask = blend.mask.as_ref().map(|mask| {
+                let len = self.width as usize;
+                &mask.as_raw()[y as usize * len..(y as usize + 1) * len]
+            });
+            blend_pixels(row, &original, mask, blend, self.decoder.as_ref());
+        }
+    }
+
+    /// Prepares an image of the row and rows around it if the row needs to be prepared.
+    fn prepare(&self, row: &[u8], above: Option<&[u8]>, below: Option<&[u8]>) -> Option<RgbImage> {
+        if self.decoder.is_none() && !self.options.detects_edges() {
+            return None;
+        }
+
+        // NOTE: Missing rows are clamped as the edge map does on borders
+        let rows = if self.needs_neighbors() {
+            vec![above.unwrap_or(row), row, below.unwrap_or(row)]
+        } else {
+            vec![row]
+        };
+        let image = RgbImage::from_raw(self.width, rows.len() as u32, rows.concat()).unwrap();
+        prepare(&image, self.decoder.as_ref(), self.options)
+    }
+}
+
+/// Iterator of rows read from a reader, keeping an error to stop at.
+struct ReadRows<'a, R> {
+    reader: R,
+    len: usize,
+    error: &'a mut Option<io::Error>,
+}
+
+impl<'a, R: Read> Iterator for ReadRows<'a, R> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        if self.len == 0 || self.error.is_some() {
+            return None;
+        }
+
+        let mut row = vec![0; self.len];
+        match read_row(&mut self.reader, &mut row) {
+            Ok(true) => Some(row),
+            Ok(false) => None,
+            Err(error) => {
+                *self.error = Some(error);
+                None
+            }
+        }
+    }
+}
+
+/// Reads a whole row, or returns `false` at the end of the stream.
+fn read_row(reader: &mut impl Read, row: &mut [u8]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < row.len() {
+        match reader.read(&mut row[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => filled += n,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(true)
+}
+
+/// Sorts columns in strips, transposing each strip to sort its columns as rows.
+fn sort_strips<S: Read + Write + Seek>(
+    stream: &mut S,
+    width: u32,
+    height: u32,
+    options: &Options,
+    budget: usize,
+) -> io::Result<()> {
+    let decoder = Decoder::new(&options.transfer);
+    let transposed = transpose_options(options);
+    let keys = Keys::new(&transposed.key, &transposed.tie, decoder.as_ref());
+    let mut sorter = LineSorter::new(&transposed);
+
+    // NOTE: Edge maps need a column on each side to be identical to one of the whole image
+    let halo = if options.detects_edges() { 1 } else { 0 };
+    let columns = strip_columns(width, height, halo, budget);
+
+    // NOTE: Sorted strips are written after reading the next strip to keep its halo unsorted
+    let mut pending: Option<(u32, RgbImage)> = None;
+    for x0 in (0..width).step_by(columns as usize) {
+        let x1 = (x0 + columns).min(width);
+        let (xa, xb) = (x0.saturating_sub(halo), (x1 + halo).min(width));
+
+        let strip = read_strip(stream, width, height, xa, xb)?;
+        if let Some((x, sorted)) = pending.take() {
+            write_strip(stream, width, x, &sorted)?;
+        }
+
+        let source = match &options.guide {
+            Guide::Image(image) => Some(crop_imm(image, xa, 0, xb - xa, height).to_image()),
+            _ => None,
+        };
+        let prepared = prepare(source.as_ref().unwrap_or(&strip), decoder.as_ref(), options);
+        let inner = |image: &RgbImage| transpose(&crop_imm(image, x0 - xa, 0, x1 - x0, height));
+        let guide = prepared.or(source).map(|guide| inner(&guide));
+
+        let mut rows = inner(&strip);
+        let stride = height as usize * 3;
+        for (i, row) in rows.chunks_exact_mut(stride).enumerate() {
+            let guide = guide
+                .as_ref()
+                .map(|guide| &guide.as_raw()[i * stride..(i + 1) * stride]);
+            sorter.sort_row(&keys, row, None, guide, x0 + i as u32);
+        }
+        drop(guide);
+
+        let mut sorted = transpose(&rows);
+        drop(rows);
+        if !options.blend.is_noop() {
+            let original = crop_imm(&strip, x0 - xa, 0, x1 - x0, height).to_image();
+            let mask = options
+                .blend
+                .mask
+                .as_ref()
+                .map(|mask| crop_imm(mask, x0, 0, x1 - x0, height).to_image());
+            let mask = mask.as_ref().map(|mask| &mask.as_raw()[..]);
+            blend_pixels(
+                &mut sorted,
+                &original,
+                mask,
+                &options.blend,
+                decoder.as_ref(),
+            );
+        }
+
+        pending = Some((x0, sorted));
+    }
+
+    if let Some((x, sorted)) = pending {
+        write_strip(stream, width, x, &sorted)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the number of columns of strips, so copies of a strip held at once fit in `budget`
+/// bytes, at least one.
+///
+/// At most four copies are held at once: the strip with its halo, a crop of the guide image and
+/// its prepared copy to detect intervals on, and the transposed guide. Transposed rows, sorted
+/// rows and the strip pending to be written are held after some of them are dropped.
+fn strip_columns(width: u32, height: u32, halo: u32, budget: usize) -> u32 {
+    const COPIES: usize = 4;
+    let column = height as usize * 3 * COPIES;
+    let columns = (budget / column).saturating_sub(2 * halo as usize);
+    columns.clamp(1, width as usize) as u32
+}
+
+/// Sorts rows one at a time, reading the next row before writing the sorted one.
+fn sort_rows_in_place<S: Read + Write + Seek>(
+    stream: &mut S,
+    width: u32,
+    height: u32,
+    options: &Options,
+) -> io::Result<()> {
+    let mut sorter = RowSorter::new(width, options);
+    let len = width as usize * 3;
+
+    let read_at = |stream: &mut S, y: u32| -> io::Result<Vec<u8>> {
+        let mut row = vec![0; len];
+        stream.seek(SeekFrom::Start(y as u64 * len as u64))?;
+        stream.read_exact(&mut row)?;
+        Ok(row)
+    };
+
+    let mut above = None;
+    let mut next = Some(read_at(stream, 0)?);
+    for y in 0..height {
+        let mut row = next.take().unwrap();
+        if y + 1 < height {
+            next = Some(read_at(stream, y + 1)?);
+        }
+
+        let unsorted = sorter.needs_neighbors().then(|| row.clone());
+        sorter.sort(&mut row, y, above.as_deref(), next.as_deref());
+
+        stream.seek(SeekFrom::Start(y as u64 * len as u64))?;
+        stream.write_all(&row)?;
+        above = unsorted;
+    }
+
+    Ok(())
+}
+
+/// Reads columns from `xa` to `xb` as an image.
+fn read_strip<S: Read + Seek>(
+    stream: &mut S,
+    width: u32,
+    height: u32,
+    xa: u32,
+    xb: u32,
+) -> io::Result<RgbImage> {
+    let len = (xb - xa) as usize * 3;
+    let mut strip = vec![0; len * height as usize];
+    for (y, row) in strip.chunks_exact_mut(len).enumerate() {
+        stream.seek(SeekFrom::Start(offset_of(width, xa, y as u32)))?;
+        stream.read_exact(row)?;
+    }
+    Ok(RgbImage::from_raw(xb - xa, height, strip).unwrap())
+}
+
+/// Writes the strip to columns from `x0`.
+fn write_strip<S: Write + Seek>(
+    stream: &mut S,
+    width: u32,
+    x0: u32,
+    strip: &RgbImage,
+) -> io::Result<()> {
+    let len = strip.width() as usize * 3;
+    for (y, row) in strip.chunks_exact(len).enumerate() {
+        stream.seek(SeekFrom::Start(offset_of(width, x0, y as u32)))?;
+        stream.write_all(row)?;
+    }
+    Ok(())
+}
+
+/// Returns the offset of the pixel in raw RGB subpixels.
+fn offset_of(width: u32, x: u32, y: u32) -> u64 {
+    (y as u64 * width as u64 + x as u64) * 3
+}
+
+/// Returns RGB subpixels of the row `y`.
+fn row_of(image: &RgbImage, y: u32) -> &[u8] {
+    let stride = image.width() as usize * 3;
+    &image.as_raw()[y as usize * stride..(y as usize + 1) * stride]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{sort_with_options, Blend, BlendMode, Channels, Key, Mode, Transfer, Variation};
+    use image::{GrayImage, Luma};
+    use std::io::Cursor;
+
+    fn load() -> RgbImage {
+        image::open("tests/p1.bmp").unwrap().to_rgb8()
+    }
+
+    fn expected(options: &Options) -> RgbImage {
+        let mut buf = load();
+        sort_with_options(&mut buf, options);
+        buf
+    }
+
+    fn streamed(options: &Options) -> RgbImage {
+        let buf = load();
+        let stride = buf.width() as usize * 3;
+        let rows = buf.as_raw().chunks(stride).map(<[u8]>::to_vec);
+        let sorted: Vec<u8> = sort_rows(rows, buf.width(), options).flatten().collect();
+        RgbImage::from_raw(buf.width(), buf.height(), sorted).unwrap()
+    }
+
+    fn tiled(options: &Options, columns: usize) -> RgbImage {
+        let buf = load();
+        let (width, height) = buf.dimensions();
+        let mut stream = Cursor::new(buf.into_raw());
+        let budget = columns * height as usize * 3 * 4;
+        sort_tiled(&mut stream, width, height, options, budget).unwrap();
+        RgbImage::from_raw(width, height, stream.into_inner()).unwrap()
+    }
+
+    fn key_image() -> GrayImage {
+        let (width, height) = load().dimensions();
+        GrayImage::from_fn(width, height, |x, y| Luma([((x * 7 + y * 3) % 256) as u8]))
+    }
+
+    fn blend() -> Blend {
+        let image = load();
+        Blend {
+            mode: BlendMode::Difference,
+            opacity: 192,
+            mask: Some(GrayImage::from_fn(image.width(), image.height(), |x, y| {
+                Luma([(x + y) as u8])
+            })),
+        }
+    }
+
+    fn guide_image() -> RgbImage {
+        let mut guide = load();
+        image::imageops::flip_horizontal_in_place(&mut guide);
+        guide
+    }
+
+    #[test]
+    fn test_sort_rows() {
+        let row = |options: Options| Options {
+            direction: Direction::Row,
+            ..options
+        };
+
+        let options = row(Default::default());
+        assert!(expected(&options) == streamed(&options), "default");
+
+        let options = row(Options {
+            mode: Mode::edge(),
+            transfer: Transfer::Srgb,
+            ..Default::default()
+        });
+        assert!(expected(&options) == streamed(&options), "edge");
+
+        let options = row(Options {
+            mode: Mode::edge(),
+            guide: Guide::Original,
+            ..Default::default()
+        });
+        assert!(expected(&options) == streamed(&options), "guide original");
+
+        let options = row(Options {
+            mode: Mode::edge(),
+            guide: Guide::Image(guide_image()),
+            ..Default::default()
+        });
+        assert!(expected(&options) == streamed(&options), "guide image");
+
+        let options = row(Options {
+            variation: Variation::Jitter {
+                amount: 30,
+                seed: 7,
+            },
+            key: Key::Image(key_image()),
+            ..Default::default()
+        });
+        assert!(expected(&options) == streamed(&options), "jitter and key");
+
+        let options = row(Options {
+            channels: Channels::Separate([Mode::edge(), Mode::brightness(), Mode::white()]),
+            ..Default::default()
+        });
+        assert!(expected(&options) == streamed(&options), "channels");
+
+        let options = row(Options {
+            blend: blend(),
+            ..Default::default()
+        });
+        assert!(expected(&options) == streamed(&options), "blend");
+
+        let options = row(Options {
+            feather: 5,
+            ..Default::default()
+        });
+        assert!(expected(&options) == streamed(&options), "feather");
+    }
+
+    #[test]
+    #[should_panic(expected = "Only rows can be sorted in streaming")]
+    fn test_sort_rows_direction() {
+        sort_rows(vec![vec![0; 3]], 1, &Options::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "Row must have RGB subpixels of the width")]
+    fn test_sort_rows_length() {
+        let options = Options {
+            direction: Direction::Row,
+            ..Default::default()
+        };
+        sort_rows(vec![vec![0; 4]], 1, &options).for_each(drop);
+    }
+
+    #[test]
+    fn test_sort_rows_from() {
+        let options = Options {
+            direction: Direction::Row,
+            ..Default::default()
+        };
+        let buf = load();
+        let mut sorted = Vec::new();
+        sort_rows_from(buf.as_raw().as_slice(), &mut sorted, buf.width(), &options).unwrap();
+        assert!(expected(&options).as_raw() == &sorted);
+
+        let error = sort_rows_from(&[0u8; 7][..], io::sink(), 2, &options).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, error.kind(), "incomplete row");
+    }
+
+    #[test]
+    fn test_sort_tiled() {
+        let options = Options::default();
+        assert!(expected(&options) == tiled(&options, 50), "default");
+        assert!(expected(&options) == tiled(&options, 1), "single column");
+        assert!(expected(&options) == tiled(&options, 1000), "whole image");
+
+        let options = Options {
+            mode: Mode::edge(),
+            transfer: Transfer::Srgb,
+            ..Default::default()
+        };
+        assert!(expected(&options) == tiled(&options, 50), "edge");
+
+        let options = Options {
+            mode: Mode::edge(),
+            direction: Direction::Column,
+            guide: Guide::Original,
+            ..Default::default()
+        };
+        assert!(expected(&options) == tiled(&options, 64), "guide original");
+
+        let options = Options {
+            mode: Mode::black(),
+            guide: Guide::Image(guide_image()),
+            variation: Variation::Linear {
+                from: (0, 0),
+                to: (700, 300),
+                end: Mode::Black(crate::PColor::new(100, 100, 100)),
+            },
+            ..Default::default()
+        };
+        assert!(expected(&options) == tiled(&options, 33), "guide image");
+
+        let options = Options {
+            variation: Variation::Jitter {
+                amount: 30,
+                seed: 7,
+            },
+            key: Key::Image(key_image()),
+            ..Default::default()
+        };
+        assert!(expected(&options) == tiled(&options, 50), "jitter and key");
+
+        let options = Options {
+            channels: Channels::Separate([Mode::edge(), Mode::brightness(), Mode::white()]),
+            ..Default::default()
+        };
+        assert!(expected(&options) == tiled(&options, 50), "channels");
+
+        let options = Options {
+            mode: Mode::edge(),
+            direction: Direction::Column,
+            blend: blend(),
+            ..Default::default()
+        };
+        assert!(expected(&options) == tiled(&options, 64), "blend");
+    }
+
+    #[test]
+    fn test_strip_columns() {
+        let column = 100 * 3;
+        assert_eq!(10, strip_columns(500, 100, 0, column * 40));
+        assert_eq!(8, strip_columns(500, 100, 1, column * 40), "halo");
+        assert_eq!(1, strip_columns(500, 100, 0, column), "at least one");
+        assert_eq!(
+            500,
+            strip_columns(500, 100, 0, usize::MAX),
+            "at most the width"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Original guide can't be used in both directions")]
+    fn test_sort_tiled_guide_original() {
+        let options = Options {
+            guide: Guide::Original,
+            ..Default::default()
+        };
+        sort_tiled(&mut Cursor::new(vec![0; 3]), 1, 1, &options, 0).unwrap();
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_sort_tiled_blend() {
+        let options = Options {
+            blend: Blend {
+                opacity: 128,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        sort_tiled(&mut Cursor::new(vec![0; 3]), 1, 1, &options, 0).unwrap();
+    }
+}
@@ -0,0 +1,437 @@
+use std::mem::discriminant;
+
+use crate::{Mode, PColor, Variation};
+
+/// Source of a threshold which may vary by pixel position.
+pub(crate) trait Threshold<T> {
+    /// Returns a threshold at the given position.
+    fn at(&self, x: u32, y: u32) -> T;
+
+    /// Returns the threshold if it doesn't vary along the line.
+    fn uniform(&self) -> Option<T>;
+}
+
+impl Threshold<u8> for u8 {
+    fn at(&self, _x: u32, _y: u32) -> u8 {
+        *self
+    }
+
+    fn uniform(&self) -> Option<u8> {
+        Some(*self)
+    }
+}
+
+impl Threshold<PColor> for PColor {
+    fn at(&self, _x: u32, _y: u32) -> PColor {
+        self.clone()
+    }
+
+    fn uniform(&self) -> Option<PColor> {
+        Some(self.clone())
+    }
+}
+
+/// Threshold value which can be blended and shifted.
+pub(crate) trait Level: Clone + Sized {
+    /// Extracts a threshold of the same kind from the given mode.
+    fn from_mode(mode: &Mode) -> Option<Self>;
+
+    /// Blends toward `other` by `t` (`0` is `self`, `255` is `other`).
+    fn blend(&self, other: &Self, t: u8) -> Self;
+
+    /// Shifts by `delta` with saturation.
+    fn shift(&self, delta: i16) -> Self;
+}
+
+impl Level for u8 {
+    fn from_mode(mode: &Mode) -> Option<Self> {
+        match mode {
+            Mode::Brightness(value) => Some(*value),
+            Mode::Edge(value) => Some(255 - value),
+            _ => None,
+        }
+    }
+
+    fn blend(&self, other: &Self, t: u8) -> Self {
+        let (a, b, t) = (*self as u32, *other as u32, t as u32);
+        ((a * (255 - t) + b * t + 127) / 255) as u8
+    }
+
+    fn shift(&self, delta: i16) -> Self {
+        (*self as i16 + delta).clamp(0, 255) as u8
+    }
+}
+
+impl Level for PColor {
+    fn from_mode(mode: &Mode) -> Option<Self> {
+        match mode {
+            Mode::Black(color) | Mode::White(color) => Some(color.clone()),
+            _ => None,
+        }
+    }
+
+    fn blend(&self, other: &Self, t: u8) -> Self {
+        PColor::new(
+            self.red.blend(&other.red, t),
+            self.green.blend(&other.green, t),
+            self.blue.blend(&other.blue, t),
+        )
+        .with_alpha(self.alpha)
+    }
+
+    fn shift(&self, delta: i16) -> Self {
+        PColor::new(
+            self.red.shift(delta),
+            self.green.shift(delta),
+            self.blue.shift(delta),
+        )
+        .with_alpha(self.alpha)
+    }
+}
+
+/// Threshold of a mode localized by [`Variation`].
+pub(crate) struct Local<'a, T> {
+    base: &'a T,
+    mode: &'a Mode,
+    end: Option<T>,
+    variation: &'a Variation,
+    line: u32,
+}
+
+impl<'a, T: Level> Local<'a, T> {
+    /// Creates a localized threshold `base` of `mode` for the line at `line`, a row index or
+    /// a column index.
+    ///
+    /// The end of a gradient or a map is ignored unless it is the same kind as `mode`.
+    pub(crate) fn new(mode: &'a Mode, base: &'a T, variation: &'a Variation, line: u32) -> Self {
+        let end = match variation {
+            Variation::Linear { end, .. } | Variation::Radial { end, .. } => Some(end),
+            Variation::Map { end, .. } => end.as_ref(),
+            _ => None,
+        };
+        let end = end
+            .filter(|end| discriminant(*end) == discriminant(mode))
+            .and_then(T::from_mode);
+
+        Self {
+            base,
+            mode,
+            end,
+            variation,
+            line,
+        }
+    }
+}
+
+impl<'a, T: Level> Threshold<T> for Local<'a, T> {
+    fn at(&self, x: u32, y: u32) -> T {
+        match (self.variation, &self.end) {
+            (Variation::Linear { from, to, .. }, Some(end)) => {
+                self.base.blend(end, linear_factor(*from, *to, x, y))
+            }
+            (Variation::Radial { center, radius, .. }, Some(end)) => {
+                self.base.blend(end, radial_factor(*center, *radius, x, y))
+            }
+            (Variation::Map { map, .. }, Some(end)) => {
+                self.base.blend(end, map.get_pixel(x, y).0[0])
+            }
+            (Variation::Map { map, end: None }, _) => {
+                let mode = with_threshold(self.mode, map.get_pixel(x, y).0[0]);
+                T::from_mode(&mode).unwrap_or_else(|| self.base.clone())
+            }
+            (Variation::Jitter { amount, seed }, _) => {
+                self.base.shift(jitter_offset(*amount, *seed, self.line))
+            }
+            _ => self.base.clone(),
+        }
+    }
+
+    fn uniform(&self) -> Option<T> {
+        match (self.variation, &self.end) {
+            (
+                Variation::Linear { .. } | Variation::Radial { .. } | Variation::Map { .. },
+                Some(_),
+            ) => None,
+            (Variation::Map { end: None, .. }, _) => None,
+            _ => Some(self.at(0, 0)),
+        }
+    }
+}
+
+/// Returns a mode of the same kind with a threshold `value`, a gray color for color modes.
+fn with_threshold(mode: &Mode, value: u8) -> Mode {
+    match mode {
+        Mode::Black(_) => Mode::Black(PColor::new(value, value, value)),
+        Mode::Brightness(_) => Mode::Brightness(value),
+        Mode::White(_) => Mode::White(PColor::new(value, value, value)),
+        Mode::Edge(_) => Mode::Edge(value),
+    }
+}
+
+/// Computes a blend factor of the position projected on the segment `from` to `to`.
+pub(crate) fn linear_factor(from: (u32, u32), to: (u32, u32), x: u32, y: u32) -> u8 {
+    let (dx, dy) = (to.0 as f64 - from.0 as f64, to.1 as f64 - from.1 as f64);
+    let length = dx * dx + dy * dy;
+    if length == 0.0 {
+        return 0;
+    }
+
+    let (px, py) = (x as f64 - from.0 as f64, y as f64 - from.1 as f64);
+    let t = (px * dx + py * dy) / length;
+    (t.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Computes a blend factor of the distance from `center` relative to `radius`.
+pub(crate) fn radial_factor(center: (u32, u32), radius: u32, x: u32, y: u32) -> u8 {
+    let (dx, dy) = (x as f64 - center.0 as f64, y as f64 - center.1 as f64);
+    let distance = (dx * dx + dy * dy).sqrt();
+    if radius == 0 {
+        return if distance == 0.0 { 0 } else { 255 };
+    }
+
+    let t = distance / radius as f64;
+    (t.min(1.0) * 255.0).round() as u8
+}
+
+/// Computes a pseudo-random offset within `-amount` to `amount` for the line.
+fn jitter_offset(amount: u8, seed: u64, line: u32) -> i16 {
+    let span = 2 * amount as u64 + 1;
+    (splitmix64(seed ^ line as u64) % span) as i16 - amount as i16
+}
+
+/// SplitMix64 hash to generate reproducible pseudo-random numbers.
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::GrayImage;
+
+    #[test]
+    fn test_level_blend_u8() {
+        assert_eq!(10, 10.blend(&200, 0));
+        assert_eq!(200, 10.blend(&200, 255));
+        assert_eq!(105, 10.blend(&200, 128));
+        assert_eq!(200, 200.blend(&10, 0));
+    }
+
+    #[test]
+    fn test_level_shift_u8() {
+        assert_eq!(70, 60.shift(10));
+        assert_eq!(50, 60.shift(-10));
+        assert_eq!(255, 250.shift(10));
+        assert_eq!(0, 5.shift(-10));
+    }
+
+    #[test]
+    fn test_level_blend_pcolor() {
+        let c1 = PColor::new(0, 100, 200).with_alpha(128);
+        let c2 = PColor::new(200, 100, 0);
+        assert_eq!(c1, c1.blend(&c2, 0));
+        assert_eq!(c2.clone().with_alpha(128), c1.blend(&c2, 255));
+        assert_eq!(
+            PColor::new(100, 100, 100).with_alpha(128),
+            c1.blend(&c2, 128)
+        );
+    }
+
+    #[test]
+    fn test_level_from_mode() {
+        assert_eq!(Some(60), u8::from_mode(&Mode::brightness()));
+        assert_eq!(None, u8::from_mode(&Mode::black()));
+        assert_eq!(Some(191), u8::from_mode(&Mode::edge()), "inverted");
+        assert_eq!(
+            Some(PColor::new(11, 220, 0)),
+            PColor::from_mode(&Mode::black())
+        );
+        assert_eq!(None, PColor::from_mode(&Mode::brightness()));
+    }
+
+    #[test]
+    fn test_linear_factor() {
+        assert_eq!(0, linear_factor((0, 0), (10, 0), 0, 5));
+        assert_eq!(128, linear_factor((0, 0), (10, 0), 5, 5));
+        assert_eq!(255, linear_factor((0, 0), (10, 0), 10, 0));
+        assert_eq!(255, linear_factor((0, 0), (10, 0), 20, 0), "beyond the end");
+        assert_eq!(0, linear_factor((10, 0), (0, 0), 20, 0), "before the start");
+        assert_eq!(0, linear_factor((3, 3), (3, 3), 5, 5), "degenerated");
+    }
+
+    #[test]
+    fn test_radial_factor() {
+        assert_eq!(0, radial_factor((5, 5), 10, 5, 5));
+        assert_eq!(128, radial_factor((5, 5), 10, 10, 5));
+        assert_eq!(255, radial_factor((5, 5), 10, 5, 15));
+        assert_eq!(
+            255,
+            radial_factor((5, 5), 10, 20, 20),
+            "outside of the circle"
+        );
+        assert_eq!(0, radial_factor((5, 5), 0, 5, 5), "zero radius");
+        assert_eq!(255, radial_factor((5, 5), 0, 6, 5), "zero radius");
+    }
+
+    #[test]
+    fn test_jitter_offset() {
+        for line in 0..100 {
+            let offset = jitter_offset(8, 42, line);
+            assert!((-8..=8).contains(&offset));
+            assert_eq!(offset, jitter_offset(8, 42, line), "reproducible");
+        }
+        assert_eq!(0, jitter_offset(0, 42, 7));
+    }
+
+    #[test]
+    fn test_local_uniform() {
+        let variation = Variation::Uniform;
+        let local = Local::new(&Mode::Brightness(60), &60u8, &variation, 0);
+        assert_eq!(60, local.at(0, 0));
+        assert_eq!(60, local.at(100, 100));
+    }
+
+    #[test]
+    fn test_local_linear() {
+        let variation = Variation::Linear {
+            from: (0, 0),
+            to: (0, 100),
+            end: Mode::Brightness(160),
+        };
+        let local = Local::new(&Mode::Brightness(60), &60u8, &variation, 0);
+        assert_eq!(60, local.at(50, 0));
+        assert_eq!(110, local.at(50, 50));
+        assert_eq!(160, local.at(50, 100));
+    }
+
+    #[test]
+    fn test_local_mismatched_end() {
+        let variation = Variation::Radial {
+            center: (0, 0),
+            radius: 10,
+            end: Mode::black(),
+        };
+        let local = Local::new(&Mode::Brightness(60), &60u8, &variation, 0);
+        assert_eq!(60, local.at(10, 10), "ignore end of different mode");
+    }
+
+    #[test]
+    fn test_local_map() {
+        let map = GrayImage::from_raw(3, 1, vec![0, 60, 255]).unwrap();
+        let variation = Variation::Map {
+            map,
+            end: Some(Mode::Brightness(255)),
+        };
+        let local = Local::new(&Mode::Brightness(0), &0u8, &variation, 0);
+        assert_eq!(0, local.at(0, 0));
+        assert_eq!(60, local.at(1, 0));
+        assert_eq!(255, local.at(2, 0));
+
+        let map = GrayImage::from_raw(3, 1, vec![0, 128, 80]).unwrap();
+        let variation = Variation::Map {
+            map,
+            end: Some(Mode::Brightness(200)),
+        };
+        let local = Local::new(&Mode::Brightness(100), &100u8, &variation, 0);
+        assert_eq!(100, local.at(0, 0));
+        assert_eq!(150, local.at(1, 0), "blends toward the end");
+        assert_eq!(131, local.at(2, 0));
+
+        let map = GrayImage::from_raw(2, 1, vec![0, 255]).unwrap();
+        let variation = Variation::Map {
+            map,
+            end: Some(Mode::White(PColor::new(200, 200, 200))),
+        };
+        let base = PColor::new(100, 0, 50);
+        let mode = Mode::White(base.clone());
+        let local = Local::new(&mode, &base, &variation, 0);
+        assert_eq!(base, local.at(0, 0));
+        assert_eq!(PColor::new(200, 200, 200), local.at(1, 0));
+    }
+
+    #[test]
+    fn test_local_map_direct() {
+        let map = GrayImage::from_raw(2, 1, vec![30, 200]).unwrap();
+        let variation = Variation::Map { map, end: None };
+        let local = Local::new(&Mode::Brightness(100), &100u8, &variation, 0);
+        assert_eq!(30, local.at(0, 0), "map values as thresholds");
+        assert_eq!(200, local.at(1, 0));
+        assert_eq!(None, local.uniform());
+
+        let local = Local::new(&Mode::Edge(100), &155u8, &variation, 0);
+        assert_eq!(225, local.at(0, 0), "inverted");
+
+        let base = PColor::new(100, 0, 50);
+        let mode = Mode::White(base.clone());
+        let local = Local::new(&mode, &base, &variation, 0);
+        assert_eq!(PColor::new(30, 30, 30), local.at(0, 0), "gray colors");
+    }
+
+    #[test]
+    fn test_local_other_kind() {
+        let map = GrayImage::from_raw(2, 1, vec![0, 255]).unwrap();
+        let variation = Variation::Map {
+            map: map.clone(),
+            end: Some(Mode::Brightness(200)),
+        };
+        let local = Local::new(&Mode::Edge(100), &155u8, &variation, 0);
+        assert_eq!(155, local.at(1, 0), "not varied toward brightness");
+        assert_eq!(Some(155), local.uniform());
+
+        let variation = Variation::Map {
+            map,
+            end: Some(Mode::White(PColor::new(200, 200, 200))),
+        };
+        let base = PColor::new(100, 0, 50);
+        let mode = Mode::Black(base.clone());
+        let local = Local::new(&mode, &base, &variation, 0);
+        assert_eq!(base, local.at(1, 0), "not varied toward white");
+    }
+
+    #[test]
+    fn test_local_jitter() {
+        let variation = Variation::Jitter {
+            amount: 10,
+            seed: 1,
+        };
+        let local = Local::new(&Mode::Brightness(60), &60u8, &variation, 3);
+        let value = local.at(0, 3);
+        assert!((50..=70).contains(&value));
+        assert_eq!(value, local.at(99, 3), "same along the line");
+        assert_eq!(Some(value), local.uniform());
+    }
+
+    #[test]
+    fn test_local_uniform_threshold() {
+        let variation = Variation::Uniform;
+        assert_eq!(
+            Some(60),
+            Local::new(&Mode::Brightness(60), &60u8, &variation, 0).uniform()
+        );
+
+        let variation = Variation::Linear {
+            from: (0, 0),
+            to: (0, 100),
+            end: Mode::Brightness(160),
+        };
+        assert_eq!(
+            None,
+            Local::new(&Mode::Brightness(60), &60u8, &variation, 0).uniform()
+        );
+
+        let variation = Variation::Linear {
+            from: (0, 0),
+            to: (0, 100),
+            end: Mode::black(),
+        };
+        assert_eq!(
+            Some(60),
+            Local::new(&Mode::Brightness(60), &60u8, &variation, 0).uniform(),
+            "ignore end of different mode"
+        );
+    }
+}
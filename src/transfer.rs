@@ -0,0 +1,383 @@
+use image::{Rgb, RgbImage};
+
+use crate::color::srgb_to_linear;
+
+/// Transfer functions to decode pixel values to linear light.
+///
+/// When decoded, keys and thresholds are computed in linear light, so thresholds mean the same
+/// thing for images with different encodings. Pixels are moved as they are, so there is no loss
+/// by re-encoding, except ones mixed in linear light by blending.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum Transfer {
+    /// Processes encoded values as they are.
+    #[default]
+    Encoded,
+
+    /// Decodes with the sRGB transfer function.
+    Srgb,
+
+    /// Decodes with lookup tables of red, green and blue channels.
+    /// Each table maps an encoded value to linear light `0` to `65535`.
+    Tables(Box<[[u16; 256]; 3]>),
+}
+
+impl Transfer {
+    /// Creates lookup tables of a pure gamma curve.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use asdf_pixel_sort::Transfer;
+    /// let transfer = Transfer::gamma(2.2);
+    /// ```
+    pub fn gamma(gamma: f32) -> Self {
+        let curve = table_of(|x| x.powf(gamma));
+        Self::Tables(Box::new([curve, curve, curve]))
+    }
+
+    /// Creates lookup tables from tone response curves (`rTRC`, `gTRC` and `bTRC` tags)
+    /// of an ICC profile, such as one embedded in a photo.
+    ///
+    /// Returns `None` if the profile is malformed or has no supported curves.
+    /// Both `curv` and `para` curve types are supported.
+    pub fn from_icc(profile: &[u8]) -> Option<Self> {
+        let red = icc_curve(profile, b"rTRC")?;
+        let green = icc_curve(profile, b"gTRC")?;
+        let blue = icc_curve(profile, b"bTRC")?;
+        Some(Self::Tables(Box::new([red, green, blue])))
+    }
+}
+
+/// Builds a lookup table by sampling a curve over `0.0` to `1.0`.
+fn table_of(curve: impl Fn(f32) -> f32) -> [u16; 256] {
+    let mut table = [0; 256];
+    for (i, value) in table.iter_mut().enumerate() {
+        let y = curve(i as f32 / 255.0);
+        *value = (y.clamp(0.0, 1.0) * 65535.0).round() as u16;
+    }
+    table
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Reads a `s15Fixed16Number` value.
+fn read_fixed(data: &[u8], offset: usize) -> Option<f32> {
+    Some(read_u32(data, offset)? as i32 as f32 / 65536.0)
+}
+
+/// Finds a tag in the tag table of an ICC profile and returns its data.
+fn icc_tag<'a>(profile: &'a [u8], signature: &[u8; 4]) -> Option<&'a [u8]> {
+    let count = read_u32(profile, 128)? as usize;
+    for i in 0..count {
+        let entry = 132 + 12 * i;
+        if profile.get(entry..entry + 4)? == signature {
+            let offset = read_u32(profile, entry + 4)? as usize;
+            let size = read_u32(profile, entry + 8)? as usize;
+            return profile.get(offset..offset.checked_add(size)?);
+        }
+    }
+    None
+}
+
+/// Builds a lookup table from a tone response curve of an ICC profile.
+fn icc_curve(profile: &[u8], signature: &[u8; 4]) -> Option<[u16; 256]> {
+    let tag = icc_tag(profile, signature)?;
+    match tag.get(0..4)? {
+        b"curv" => {
+            let count = read_u32(tag, 8)? as usize;
+            match count {
+                0 => Some(table_of(|x| x)),
+                1 => {
+                    let gamma = read_u16(tag, 12)? as f32 / 256.0;
+                    Some(table_of(|x| x.powf(gamma)))
+                }
+                _ => {
+                    let points = (0..count)
+                        .map(|i| read_u16(tag, 12 + 2 * i))
+                        .collect::<Option<Vec<_>>>()?;
+                    Some(table_of(|x| {
+                        let position = x * (count - 1) as f32;
+                        let i = (position.floor() as usize).min(count - 2);
+                        let t = position - i as f32;
+                        let (a, b) = (points[i] as f32, points[i + 1] as f32);
+                        (a + (b - a) * t) / 65535.0
+                    }))
+                }
+            }
+        }
+        b"para" => {
+            let function = read_u16(tag, 8)?;
+            let arity = match function {
+                0 => 1,
+                1 => 3,
+                2 => 4,
+                3 => 5,
+                4 => 7,
+                _ => return None,
+            };
+            let p = (0..arity)
+                .map(|i| read_fixed(tag, 12 + 4 * i))
+                .collect::<Option<Vec<_>>>()?;
+            Some(table_of(|x| match function {
+                0 => x.powf(p[0]),
+                1 if x >= -p[2] / p[1] => (p[1] * x + p[2]).powf(p[0]),
+                1 => 0.0,
+                2 if x >= -p[2] / p[1] => (p[1] * x + p[2]).powf(p[0]) + p[3],
+                2 => p[3],
+                3 if x >= p[4] => (p[1] * x + p[2]).powf(p[0]),
+                3 => p[3] * x,
+                _ if x >= p[4] => (p[1] * x + p[2]).powf(p[0]) + p[5],
+                _ => p[3] * x + p[6],
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// Channels of tables to decode red, green and blue subpixels of a pixel with.
+pub(crate) const RGB: [usize; 3] = [0, 1, 2];
+
+/// Decoder of pixels to linear light.
+pub(crate) struct Decoder {
+    tables: [[f32; 256]; 3],
+}
+
+impl Decoder {
+    /// Creates a decoder of the transfer, or `None` if pixels are processed as they are.
+    pub(crate) fn new(transfer: &Transfer) -> Option<Self> {
+        let mut tables = [[0.0; 256]; 3];
+        match transfer {
+            Transfer::Encoded => return None,
+            Transfer::Srgb => {
+                for table in tables.iter_mut() {
+                    for (i, value) in table.iter_mut().enumerate() {
+                        *value = srgb_to_linear(i as u8);
+                    }
+                }
+            }
+            Transfer::Tables(sources) => {
+                for (table, source) in tables.iter_mut().zip(sources.iter()) {
+                    for (value, source) in table.iter_mut().zip(source.iter()) {
+                        *value = *source as f32 / 65535.0;
+                    }
+                }
+            }
+        }
+        Some(Self { tables })
+    }
+
+    /// Decodes a pixel to linear light `(r, g, b)` between `0.0` to `1.0`, subpixels with
+    /// tables of `channels`, [`RGB`] or the same channel for a channel plane.
+    pub(crate) fn decode(&self, pixel: &Rgb<u8>, channels: [usize; 3]) -> (f32, f32, f32) {
+        let [r, g, b] = pixel.0;
+        (
+            self.decode_value(channels[0], r),
+            self.decode_value(channels[1], g),
+            self.decode_value(channels[2], b),
+        )
+    }
+
+    /// Decodes a value of the channel to linear light between `0.0` to `1.0`.
+    pub(crate) fn decode_value(&self, channel: usize, value: u8) -> f32 {
+        self.tables[channel][value as usize]
+    }
+
+    /// Encodes linear light of the channel to the value decoded to the nearest light.
+    pub(crate) fn encode_value(&self, channel: usize, linear: f32) -> u8 {
+        let table = &self.tables[channel];
+        let i = table.partition_point(|&value| value < linear);
+        if i == 0 {
+            return 0;
+        }
+        if i == table.len() {
+            return 255;
+        }
+        let nearer = if linear - table[i - 1] <= table[i] - linear {
+            i - 1
+        } else {
+            i
+        };
+        nearer as u8
+    }
+
+    /// Decodes a pixel to linear light quantized to `0` to `255` as [`Decoder::decode()`].
+    pub(crate) fn decode8(&self, pixel: &Rgb<u8>, channels: [usize; 3]) -> Rgb<u8> {
+        let (r, g, b) = self.decode(pixel, channels);
+        let quantize = |c: f32| (c * 255.0).round() as u8;
+        Rgb([quantize(r), quantize(g), quantize(b)])
+    }
+
+    /// Decodes all pixels of the image to linear light quantized to `0` to `255`.
+    pub(crate) fn decode_image(&self, image: &RgbImage) -> RgbImage {
+        let mut linear = image.clone();
+        for pixel in linear.pixels_mut() {
+            *pixel = self.decode8(pixel, RGB);
+        }
+        linear
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a minimal ICC profile having the given tags.
+    fn profile_of(tags: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let mut profile = vec![0; 128];
+        profile.extend_from_slice(&(tags.len() as u32).to_be_bytes());
+        let mut offset = 132 + 12 * tags.len();
+        for (signature, data) in tags {
+            profile.extend_from_slice(*signature);
+            profile.extend_from_slice(&(offset as u32).to_be_bytes());
+            profile.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            offset += data.len();
+        }
+        for (_, data) in tags {
+            profile.extend_from_slice(data);
+        }
+        profile
+    }
+
+    fn curv_of(points: &[u16]) -> Vec<u8> {
+        let mut data = b"curv\0\0\0\0".to_vec();
+        data.extend_from_slice(&(points.len() as u32).to_be_bytes());
+        for point in points {
+            data.extend_from_slice(&point.to_be_bytes());
+        }
+        data
+    }
+
+    fn para_of(function: u16, params: &[f32]) -> Vec<u8> {
+        let mut data = b"para\0\0\0\0".to_vec();
+        data.extend_from_slice(&function.to_be_bytes());
+        data.extend_from_slice(&[0, 0]);
+        for param in params {
+            data.extend_from_slice(&((param * 65536.0).round() as i32).to_be_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_transfer_default() {
+        let expected = Transfer::Encoded;
+        assert_eq!(expected, Transfer::default());
+    }
+
+    #[test]
+    fn test_transfer_gamma() {
+        let tables = match Transfer::gamma(2.0) {
+            Transfer::Tables(tables) => tables,
+            _ => unreachable!(),
+        };
+        assert_eq!(0, tables[0][0]);
+        assert_eq!(65535, tables[1][255]);
+        assert_eq!(16513, tables[2][128]);
+    }
+
+    #[test]
+    fn test_transfer_from_icc_curv() {
+        let profile = profile_of(&[
+            (b"rTRC", curv_of(&[])),
+            (b"gTRC", curv_of(&[512])),
+            (b"bTRC", curv_of(&[0, 65535, 65535])),
+        ]);
+        let tables = match Transfer::from_icc(&profile) {
+            Some(Transfer::Tables(tables)) => tables,
+            _ => unreachable!(),
+        };
+        assert_eq!(32896, tables[0][128], "identity");
+        assert_eq!(16513, tables[1][128], "gamma 2.0");
+        assert_eq!(0, tables[2][0], "table");
+        assert_eq!(65535, tables[2][128], "table");
+        assert_eq!(32896, tables[2][64], "table");
+    }
+
+    #[test]
+    fn test_transfer_from_icc_para() {
+        let srgb = para_of(3, &[2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045]);
+        let profile = profile_of(&[
+            (b"rTRC", srgb.clone()),
+            (b"gTRC", srgb.clone()),
+            (b"bTRC", para_of(0, &[1.0])),
+        ]);
+        let tables = match Transfer::from_icc(&profile) {
+            Some(Transfer::Tables(tables)) => tables,
+            _ => unreachable!(),
+        };
+        for i in 0..=255u8 {
+            let expected = (srgb_to_linear(i) * 65535.0).round() as i32;
+            assert!((expected - tables[0][i as usize] as i32).abs() <= 2);
+            assert_eq!(tables[0][i as usize], tables[1][i as usize]);
+        }
+        assert_eq!(32896, tables[2][128], "gamma 1.0");
+    }
+
+    #[test]
+    fn test_transfer_from_icc_malformed() {
+        assert_eq!(None, Transfer::from_icc(&[]));
+        assert_eq!(None, Transfer::from_icc(&[0; 200]), "no tags");
+
+        let profile = profile_of(&[(b"rTRC", curv_of(&[])), (b"gTRC", curv_of(&[]))]);
+        assert_eq!(None, Transfer::from_icc(&profile), "missing tag");
+
+        let profile = profile_of(&[
+            (b"rTRC", curv_of(&[])),
+            (b"gTRC", curv_of(&[])),
+            (b"bTRC", para_of(5, &[1.0])),
+        ]);
+        assert_eq!(None, Transfer::from_icc(&profile), "unknown function");
+
+        let mut profile = profile_of(&[
+            (b"rTRC", curv_of(&[])),
+            (b"gTRC", curv_of(&[])),
+            (b"bTRC", curv_of(&[0, 65535])),
+        ]);
+        profile.truncate(profile.len() - 2);
+        assert_eq!(None, Transfer::from_icc(&profile), "truncated");
+    }
+
+    #[test]
+    fn test_decoder_new() {
+        assert!(Decoder::new(&Transfer::Encoded).is_none());
+        assert!(Decoder::new(&Transfer::Srgb).is_some());
+        assert!(Decoder::new(&Transfer::gamma(2.2)).is_some());
+    }
+
+    #[test]
+    fn test_decoder_decode() {
+        let decoder = Decoder::new(&Transfer::Srgb).unwrap();
+        let (r, g, b) = decoder.decode(&Rgb([0, 128, 255]), RGB);
+        assert_eq!(0.0, r);
+        assert!((g - 0.215_861).abs() < 1e-6);
+        assert_eq!(1.0, b);
+
+        assert_eq!(Rgb([0, 55, 255]), decoder.decode8(&Rgb([0, 128, 255]), RGB));
+    }
+
+    #[test]
+    fn test_decoder_encode_value() {
+        let decoder = Decoder::new(&Transfer::Srgb).unwrap();
+        for value in 0..=255 {
+            let linear = decoder.decode_value(1, value);
+            assert_eq!(value, decoder.encode_value(1, linear));
+        }
+        assert_eq!(188, decoder.encode_value(0, 0.5));
+        assert_eq!(0, decoder.encode_value(0, -1.0));
+        assert_eq!(255, decoder.encode_value(0, 2.0));
+    }
+
+    #[test]
+    fn test_decoder_decode_image() {
+        let decoder = Decoder::new(&Transfer::gamma(1.0)).unwrap();
+        let image = RgbImage::from_fn(3, 2, |x, y| Rgb([x as u8, y as u8, 255]));
+        assert_eq!(image, decoder.decode_image(&image));
+    }
+}
@@ -0,0 +1,166 @@
+use image::{GenericImageView, ImageBuffer, Pixel};
+
+use crate::{Blend, Guide, Key, Options, TieBreak, Variation};
+
+/// Side length of square tiles to transpose in cache-friendly order.
+const TILE: u32 = 32;
+
+/// Transposes the image or a view of it, swapping columns and rows, tile by tile.
+pub(crate) fn transpose<I, P>(image: &I) -> ImageBuffer<P, Vec<P::Subpixel>>
+where
+    I: GenericImageView<Pixel = P>,
+    P: Pixel + 'static,
+{
+    let (width, height) = image.dimensions();
+    let mut transposed = ImageBuffer::new(height, width);
+
+    for y0 in (0..height).step_by(TILE as usize) {
+        for x0 in (0..width).step_by(TILE as usize) {
+            for y in y0..(y0 + TILE).min(height) {
+                for x in x0..(x0 + TILE).min(width) {
+                    transposed.put_pixel(y, x, image.get_pixel(x, y));
+                }
+            }
+        }
+    }
+
+    transposed
+}
+
+/// Transposes spatial options to sort columns of an image as rows of the transposed image.
+///
+/// The guide and the blend are not parts of the transposed options since the guide is given to
+/// sorters separately and the blend is applied to the untransposed image.
+pub(crate) fn transpose_options(options: &Options) -> Options {
+    Options {
+        mode: options.mode.clone(),
+        direction: options.direction.clone(),
+        variation: transpose_variation(&options.variation),
+        key: transpose_key(&options.key),
+        tie: match &options.tie {
+            TieBreak::Key(key) => TieBreak::Key(transpose_key(key)),
+            tie => tie.clone(),
+        },
+        guide: Guide::Target,
+        transfer: options.transfer.clone(),
+        alpha: options.alpha,
+        channels: options.channels.clone(),
+        blend: Blend::default(),
+        feather: options.feather,
+    }
+}
+
+fn transpose_variation(variation: &Variation) -> Variation {
+    let swap = |(x, y): (u32, u32)| (y, x);
+    match variation {
+        Variation::Linear { from, to, end } => Variation::Linear {
+            from: swap(*from),
+            to: swap(*to),
+            end: end.clone(),
+        },
+        Variation::Radial {
+            center,
+            radius,
+            end,
+        } => Variation::Radial {
+            center: swap(*center),
+            radius: *radius,
+            end: end.clone(),
+        },
+        Variation::Map { map, end } => Variation::Map {
+            map: transpose(map),
+            end: end.clone(),
+        },
+        variation => variation.clone(),
+    }
+}
+
+fn transpose_key(key: &Key) -> Key {
+    match key {
+        Key::Image(image) => Key::Image(transpose(image)),
+        key => key.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Mode;
+    use image::{GrayImage, Luma, Rgb, RgbImage};
+
+    #[test]
+    fn test_transpose() {
+        let image = RgbImage::from_fn(3, 2, |x, y| Rgb([x as u8, y as u8, 0]));
+        let transposed = transpose(&image);
+        assert_eq!((2, 3), transposed.dimensions());
+        for (x, y, pixel) in transposed.enumerate_pixels() {
+            assert_eq!(Rgb([y as u8, x as u8, 0]), *pixel);
+        }
+    }
+
+    #[test]
+    fn test_transpose_tiles() {
+        let image = GrayImage::from_fn(70, 45, |x, y| Luma([(x * 3 + y * 7) as u8]));
+        let transposed = transpose(&image);
+        assert_eq!((45, 70), transposed.dimensions());
+        for (x, y, pixel) in transposed.enumerate_pixels() {
+            assert_eq!(image.get_pixel(y, x), pixel);
+        }
+        assert_eq!(image, transpose(&transposed), "involution");
+    }
+
+    #[test]
+    fn test_transpose_options() {
+        let map = GrayImage::from_fn(3, 2, |x, y| Luma([(x + 10 * y) as u8]));
+        let options = Options {
+            variation: Variation::Map {
+                map: map.clone(),
+                end: Some(Mode::Brightness(255)),
+            },
+            tie: TieBreak::Key(Key::Image(map.clone())),
+            guide: Guide::Original,
+            ..Default::default()
+        };
+        let expected = Options {
+            variation: Variation::Map {
+                map: transpose(&map),
+                end: Some(Mode::Brightness(255)),
+            },
+            tie: TieBreak::Key(Key::Image(transpose(&map))),
+            guide: Guide::Target,
+            ..Default::default()
+        };
+        assert_eq!(expected, transpose_options(&options));
+    }
+
+    #[test]
+    fn test_transpose_variation() {
+        let variation = Variation::Linear {
+            from: (1, 2),
+            to: (3, 4),
+            end: Mode::Brightness(0),
+        };
+        let expected = Variation::Linear {
+            from: (2, 1),
+            to: (4, 3),
+            end: Mode::Brightness(0),
+        };
+        assert_eq!(expected, transpose_variation(&variation));
+
+        let variation = Variation::Radial {
+            center: (5, 6),
+            radius: 7,
+            end: Mode::Brightness(0),
+        };
+        let expected = Variation::Radial {
+            center: (6, 5),
+            radius: 7,
+            end: Mode::Brightness(0),
+        };
+        assert_eq!(expected, transpose_variation(&variation));
+
+        let variation = Variation::Jitter { amount: 1, seed: 2 };
+        assert_eq!(variation, transpose_variation(&variation));
+    }
+}
@@ -0,0 +1,214 @@
+use image::RgbaImage;
+use js_sys::{Array, Reflect, Uint8ClampedArray};
+use wasm_bindgen::prelude::*;
+
+use crate::{sort_rgba, Alpha, Direction, Mode, Options, PColor};
+
+/// Sorts RGBA pixels of `ImageData` in place with an options object.
+///
+/// The options object has optional properties below, and defaults are the same as [`Options`].
+///
+/// - `mode`: `"brightness"`, `"black"`, `"white"` or `"edge"`
+/// - `threshold`: a number between `0` to `255` for `"brightness"` and `"edge"`
+/// - `color`: an array of red, green and blue for `"black"` and `"white"`
+/// - `direction`: `"both"`, `"column"` or `"row"`
+/// - `alpha`: `"ignore"`, `"break"`, `"exclude"` or `"premultiply"`
+///
+/// Alpha values move along with pixels, and transparent pixels are treated by `alpha`.
+///
+/// ```js
+/// const image = context.getImageData(0, 0, width, height);
+/// sort(image.data, image.width, image.height, { mode: "black", color: [11, 220, 0] });
+/// context.putImageData(image, 0, 0);
+/// ```
+#[wasm_bindgen(js_name = sort)]
+pub fn sort_image_data(
+    data: &Uint8ClampedArray,
+    width: u32,
+    height: u32,
+    options: JsValue,
+) -> Result<(), JsValue> {
+    let pixels = width as usize * height as usize;
+    if data.length() as usize != pixels * 4 {
+        return Err(error("Data must have RGBA pixels of the width and height"));
+    }
+
+    let options = options_of(&options)?;
+    let mut buf = RgbaImage::from_raw(width, height, data.to_vec()).unwrap();
+    if pixels != 0 {
+        sort_rgba(&mut buf, &options);
+    }
+    data.copy_from(&buf);
+
+    Ok(())
+}
+
+/// Reads [`Options`] from an options object, which may be `undefined` for defaults.
+fn options_of(value: &JsValue) -> Result<Options, JsValue> {
+    if value.is_undefined() || value.is_null() {
+        return Ok(Options::default());
+    }
+
+    let mode = match property(value, "mode")?.as_string().as_deref() {
+        None | Some("brightness") => Mode::Brightness(threshold_of(value, Mode::brightness())?),
+        Some("edge") => Mode::Edge(threshold_of(value, Mode::edge())?),
+        Some("black") => Mode::Black(color_of(value, Mode::black())?),
+        Some("white") => Mode::White(color_of(value, Mode::white())?),
+        Some(mode) => return Err(error(&format!("Unknown mode: {}", mode))),
+    };
+
+    let direction = match property(value, "direction")?.as_string().as_deref() {
+        None | Some("both") => Direction::Both,
+        Some("column") => Direction::Column,
+        Some("row") => Direction::Row,
+        Some(direction) => return Err(error(&format!("Unknown direction: {}", direction))),
+    };
+
+    let alpha = match property(value, "alpha")?.as_string().as_deref() {
+        None | Some("ignore") => Alpha::Ignore,
+        Some("break") => Alpha::Break,
+        Some("exclude") => Alpha::Exclude,
+        Some("premultiply") => Alpha::Premultiply,
+        Some(alpha) => return Err(error(&format!("Unknown alpha: {}", alpha))),
+    };
+
+    Ok(Options {
+        mode,
+        direction,
+        alpha,
+        ..Default::default()
+    })
+}
+
+/// Reads `threshold` property, or the threshold of the default mode if absent.
+fn threshold_of(value: &JsValue, default: Mode) -> Result<u8, JsValue> {
+    let threshold = property(value, "threshold")?;
+    if threshold.is_undefined() {
+        return match default {
+            Mode::Brightness(threshold) | Mode::Edge(threshold) => Ok(threshold),
+            _ => unreachable!(),
+        };
+    }
+
+    byte_of(&threshold, "threshold")
+}
+
+/// Reads `color` property, or the color of the default mode if absent.
+fn color_of(value: &JsValue, default: Mode) -> Result<PColor, JsValue> {
+    let color = property(value, "color")?;
+    if color.is_undefined() {
+        return match default {
+            Mode::Black(color) | Mode::White(color) => Ok(color),
+            _ => unreachable!(),
+        };
+    }
+
+    let channels = Array::from(&color);
+    if !Array::is_array(&color) || channels.length() != 3 {
+        return Err(error("Color must be an array of red, green and blue"));
+    }
+
+    Ok(PColor::new(
+        byte_of(&channels.get(0), "red")?,
+        byte_of(&channels.get(1), "green")?,
+        byte_of(&channels.get(2), "blue")?,
+    ))
+}
+
+fn byte_of(value: &JsValue, name: &str) -> Result<u8, JsValue> {
+    match value.as_f64() {
+        Some(v) if (0.0..=255.0).contains(&v) && v.fract() == 0.0 => Ok(v as u8),
+        _ => Err(error(&format!(
+            "{} must be an integer between 0 to 255",
+            name
+        ))),
+    }
+}
+
+fn property(value: &JsValue, key: &str) -> Result<JsValue, JsValue> {
+    Reflect::get(value, &JsValue::from_str(key))
+}
+
+fn error(message: &str) -> JsValue {
+    js_sys::TypeError::new(message).into()
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use super::*;
+
+    use js_sys::Object;
+    use wasm_bindgen_test::*;
+
+    fn object(properties: &[(&str, JsValue)]) -> JsValue {
+        let object = Object::new();
+        for (key, value) in properties {
+            Reflect::set(&object, &JsValue::from_str(key), value).unwrap();
+        }
+        object.into()
+    }
+
+    fn rgba(pixels: &[[u8; 4]]) -> Vec<u8> {
+        pixels.iter().flatten().copied().collect()
+    }
+
+    #[wasm_bindgen_test]
+    fn test_options_of() {
+        assert_eq!(Options::default(), options_of(&JsValue::UNDEFINED).unwrap());
+
+        let options = object(&[
+            ("mode", "black".into()),
+            ("color", Array::of3(&1.into(), &2.into(), &3.into()).into()),
+            ("direction", "row".into()),
+            ("alpha", "break".into()),
+        ]);
+        let expected = Options {
+            mode: Mode::Black(PColor::new(1, 2, 3)),
+            direction: Direction::Row,
+            alpha: Alpha::Break,
+            ..Default::default()
+        };
+        assert_eq!(expected, options_of(&options).unwrap());
+
+        let options = object(&[("mode", "edge".into())]);
+        assert_eq!(Mode::edge(), options_of(&options).unwrap().mode);
+
+        let options = object(&[("threshold", 300.into())]);
+        assert!(options_of(&options).is_err(), "out of range");
+
+        let options = object(&[("mode", "gray".into())]);
+        assert!(options_of(&options).is_err(), "unknown mode");
+
+        let options = object(&[("alpha", "keep".into())]);
+        assert!(options_of(&options).is_err(), "unknown alpha");
+    }
+
+    #[wasm_bindgen_test]
+    fn test_sort_image_data() {
+        let data = Uint8ClampedArray::from(
+            &rgba(&[
+                [255, 255, 255, 10],
+                [128, 128, 128, 20],
+                [64, 64, 64, 30],
+                [0, 0, 0, 40],
+            ])[..],
+        );
+        let options = object(&[("direction", "row".into())]);
+        sort_image_data(&data, 4, 1, options).unwrap();
+
+        let expected = rgba(&[
+            [128, 128, 128, 20],
+            [255, 255, 255, 10],
+            [64, 64, 64, 30],
+            [0, 0, 0, 40],
+        ]);
+        assert_eq!(expected, data.to_vec(), "alpha moves along");
+    }
+
+    #[wasm_bindgen_test]
+    fn test_sort_image_data_length() {
+        let data = Uint8ClampedArray::new_with_length(15);
+        let result = sort_image_data(&data, 2, 2, JsValue::UNDEFINED);
+        assert!(result.is_err());
+    }
+}